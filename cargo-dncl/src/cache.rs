@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use dncl_trans_core::cache::{entry_paths, read_entry, CachedContent};
+use dncl_trans_core::config::Config;
+
+use crate::workspace::find_invocations;
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    content: eyre::Result<CachedContent>,
+}
+
+fn load_entries(manifest_dir: &Path) -> eyre::Result<Vec<Entry>> {
+    let entries = entry_paths(&Config::load(manifest_dir)?.cache_dir())?
+        .into_iter()
+        .map(|path| Entry {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            content: read_entry(&path),
+            path,
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// キャッシュファイル名から参照元 ( `ファイル:行` ) への対応
+struct References {
+    by_name: HashMap<String, Vec<String>>,
+    /// パースに失敗した `dncl!` 呼び出し
+    failures: Vec<String>,
+}
+
+fn references(manifest_dir: &Path) -> eyre::Result<References> {
+    let config = Config::load(manifest_dir)?;
+    let api_key = dncl_trans_core::api_key(&config)
+        .map_err(|e| eyre::eyre!("API key is required to compute cache keys: {}", e))?;
+
+    let mut by_name: HashMap<String, Vec<String>> = HashMap::new();
+    let mut failures = Vec::new();
+
+    for invocation in find_invocations(manifest_dir)? {
+        let file_name = invocation
+            .input
+            .as_ref()
+            .map_err(|e| eyre::eyre!("{}", e))
+            .and_then(|input| dncl_trans_core::cache_file_name(input, &config, &api_key));
+
+        match file_name {
+            Ok(file_name) => by_name
+                .entry(file_name)
+                .or_default()
+                .push(invocation.location()),
+            Err(e) => {
+                eprintln!("warning: failed to parse {}: {}", invocation.location(), e);
+                failures.push(invocation.location());
+            }
+        }
+    }
+
+    Ok(References { by_name, failures })
+}
+
+fn unreferenced(manifest_dir: &Path) -> eyre::Result<(Vec<Entry>, References)> {
+    let references = references(manifest_dir)?;
+
+    let entries = load_entries(manifest_dir)?
+        .into_iter()
+        .filter(|entry| !references.by_name.contains_key(&entry.name))
+        .collect();
+
+    Ok((entries, references))
+}
+
+pub fn list(manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    // APIキーがなくても一覧は表示できるようにする
+    let references = match references(manifest_dir) {
+        Ok(references) => Some(references),
+        Err(e) => {
+            eprintln!("warning: {}", e);
+            None
+        }
+    };
+
+    for entry in load_entries(manifest_dir)? {
+        let content = match &entry.content {
+            Ok(content) => content,
+            Err(e) => {
+                println!("{}  (broken: {})", entry.name, e);
+                continue;
+            }
+        };
+
+        let created_at = content
+            .created_at
+            .map(|created_at| created_at.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        let referenced_by = match &references {
+            Some(references) => match references.by_name.get(&entry.name) {
+                Some(locations) => locations.join(", "),
+                None => "(unreferenced)".to_string(),
+            },
+            None => "?".to_string(),
+        };
+
+        println!(
+            "{}  model={}  seed={}  created_at={}  {}",
+            entry.name, content.model, content.seed, created_at, referenced_by
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn prune(manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    let (entries, _) = unreferenced(manifest_dir)?;
+
+    for entry in &entries {
+        println!("{}", entry.path.display());
+    }
+    eprintln!(
+        "{} unreferenced cache file(s). Run `cargo dncl cache purge` to delete them.",
+        entries.len()
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn purge(manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    let (entries, references) = unreferenced(manifest_dir)?;
+
+    // 参照元が判定できないものがあると誤って削除しかねないので中断する
+    if !references.failures.is_empty() {
+        eyre::bail!(
+            "could not parse dncl! at {}; fix them before purging",
+            references.failures.join(", ")
+        );
+    }
+
+    for entry in &entries {
+        fs::remove_file(&entry.path)?;
+
+        let rs_file = entry.path.with_extension("rs");
+        if fs::exists(&rs_file)? {
+            fs::remove_file(&rs_file)?;
+        }
+
+        println!("removed {}", entry.path.display());
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn verify(manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    let mut failed = 0;
+
+    for entry in load_entries(manifest_dir)? {
+        let result = entry.content.and_then(|content| {
+            syn::parse_file(&content.rust_code())?;
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => println!("ok      {}", entry.name),
+            Err(e) => {
+                println!("FAILED  {}: {}", entry.name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{} cache file(s) failed to verify", failed);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
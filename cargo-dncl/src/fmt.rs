@@ -0,0 +1,113 @@
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+
+use dncl_trans_core::config::Config;
+use dncl_trans_core::format::{format, FormatOptions};
+
+/// `cargo dncl fmt` の引数
+struct Args<'a> {
+    options: FormatOptions,
+    check: bool,
+    files: Vec<&'a str>,
+}
+
+fn parse_args<'a>(args: &[&'a str], config: &Config) -> eyre::Result<Args<'a>> {
+    let mut parsed = Args {
+        options: FormatOptions {
+            dialect: config.dialect,
+            ..FormatOptions::default()
+        },
+        check: false,
+        files: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--line-numbers" => parsed.options.line_numbers = true,
+            "--check" => parsed.check = true,
+            "--dialect" => {
+                let Some(dialect) = iter.next() else {
+                    eyre::bail!("--dialect requires a value");
+                };
+                parsed.options.dialect = dialect.parse().map_err(|e| eyre::eyre!("{}", e))?;
+            }
+            arg if arg.starts_with("--") => eyre::bail!("unexpected option: {}", arg),
+            file => parsed.files.push(file),
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// DNCLファイルを整形して上書きする。ファイルの指定がなければ標準入力を整形して標準出力へ書き出す
+///
+/// `--check` の場合は書き換えず、整形されていないファイルを表示して失敗する
+pub fn fmt(args: &[&str], manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    let args = parse_args(args, &Config::load(manifest_dir)?)?;
+
+    if args.files.is_empty() {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+        let formatted = format(&source, &args.options);
+
+        if args.check {
+            return Ok(match formatted == source.trim_end() {
+                true => ExitCode::SUCCESS,
+                false => ExitCode::FAILURE,
+            });
+        }
+        println!("{}", formatted);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut unformatted = 0;
+    for file in &args.files {
+        let source =
+            fs::read_to_string(file).map_err(|e| eyre::eyre!("failed to read {}: {}", file, e))?;
+        let formatted = format!("{}\n", format(&source, &args.options));
+
+        if formatted == source {
+            continue;
+        }
+        if args.check {
+            println!("{}", file);
+            unformatted += 1;
+        } else {
+            fs::write(file, formatted)?;
+            println!("formatted {}", file);
+        }
+    }
+
+    if unformatted > 0 {
+        eprintln!("{} file(s) are not formatted", unformatted);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod test {
+    use dncl_trans_core::config::{Config, Dialect};
+
+    use super::parse_args;
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse_args(
+            &["--line-numbers", "--dialect", "legacy", "problems/q1.dncl"],
+            &Config::default(),
+        )
+        .unwrap();
+
+        assert!(args.options.line_numbers);
+        assert_eq!(args.options.dialect, Dialect::Legacy);
+        assert!(!args.check);
+        assert_eq!(args.files, vec!["problems/q1.dncl"]);
+
+        assert!(parse_args(&["--dialect", "r8"], &Config::default()).is_err());
+    }
+}
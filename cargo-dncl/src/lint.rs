@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use dncl_trans_core::config::Config;
+use dncl_trans_core::lint::lint as lint_source;
+
+use crate::workspace::find_invocations;
+
+/// DNCLファイル、あるいはファイルの指定がなければクレート中の `dncl!` 呼び出しの約束事をチェックする
+///
+/// 警告があった場合は失敗する
+pub fn lint(args: &[&str], manifest_dir: &Path) -> eyre::Result<ExitCode> {
+    let mut config = Config::load(manifest_dir)?;
+    let mut files = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match *arg {
+            "--dialect" => {
+                let Some(value) = iter.next() else {
+                    eyre::bail!("--dialect requires a value");
+                };
+                // `@dialect` を指定していない `dncl!` にも適用する
+                config.dialect = value.parse().map_err(|e| eyre::eyre!("{}", e))?;
+            }
+            arg if arg.starts_with("--") => eyre::bail!("unexpected option: {}", arg),
+            file => files.push(file),
+        }
+    }
+
+    let mut count = 0;
+
+    if files.is_empty() {
+        // `@file` の相対パスを解決するため、マクロ展開時と同じくクレートのディレクトリで作業する
+        if !manifest_dir.as_os_str().is_empty() {
+            std::env::set_current_dir(manifest_dir)?;
+        }
+
+        for invocation in find_invocations(&std::env::current_dir()?)? {
+            let input = match &invocation.input {
+                Ok(input) => input,
+                Err(e) => {
+                    eprintln!("warning: failed to parse {}: {}", invocation.location(), e);
+                    continue;
+                }
+            };
+
+            for warning in input.lint_warnings(&config) {
+                println!(
+                    "{}: warning: {} (DNCL line {})",
+                    invocation.location(),
+                    warning.message,
+                    warning.line
+                );
+                count += 1;
+            }
+        }
+    }
+
+    for file in files {
+        let source =
+            fs::read_to_string(file).map_err(|e| eyre::eyre!("failed to read {}: {}", file, e))?;
+
+        for warning in lint_source(&source, config.dialect) {
+            println!("{}:{}: warning: {}", file, warning.line, warning.message);
+            count += 1;
+        }
+    }
+
+    if count > 0 {
+        eprintln!("{} warning(s)", count);
+        return Ok(ExitCode::FAILURE);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
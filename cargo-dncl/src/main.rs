@@ -0,0 +1,106 @@
+//! `cargo dncl` サブコマンド
+//!
+//! ```text
+//! cargo dncl cache list   [--manifest-path <DIR>]  キャッシュ一覧を表示
+//! cargo dncl cache prune  [--manifest-path <DIR>]  どの dncl! からも参照されていないキャッシュを表示
+//! cargo dncl cache verify [--manifest-path <DIR>]  キャッシュがRustとしてパースできるか検証
+//! cargo dncl cache purge  [--manifest-path <DIR>]  どの dncl! からも参照されていないキャッシュを削除
+//! cargo dncl fmt [--check] [--line-numbers] [--dialect <r7|legacy>] [FILE...]  DNCLファイルを整形
+//! cargo dncl lint [--dialect <r7|legacy>] [FILE...]  DNCLの約束事をチェック
+//! ```
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod cache;
+mod fmt;
+mod lint;
+mod workspace;
+
+const USAGE: &str = "\
+Usage: cargo dncl cache <list|prune|verify|purge> [--manifest-path <DIR>]
+       cargo dncl fmt [--check] [--line-numbers] [--dialect <r7|legacy>] [FILE...]
+       cargo dncl lint [--dialect <r7|legacy>] [FILE...]
+
+  list    キャッシュ一覧 (モデル・シード・作成日時・参照元) を表示
+  prune   どの dncl! からも参照されていないキャッシュを表示
+  verify  各キャッシュのRustコードがパースできるか検証
+  purge   どの dncl! からも参照されていないキャッシュを削除
+  fmt     DNCLファイルを整形して上書きする (ファイルの指定がなければ標準入力を整形して標準出力へ)
+          --check         書き換えずに、整形されていないファイルを表示する
+          --line-numbers  行頭に (1) のような行番号を付ける
+          --dialect       DNCLの文法 (デフォルトは設定ファイルの dialect)
+  lint    DNCLファイル (ファイルの指定がなければすべての dncl! ) の約束事をチェックし、警告があれば失敗する
+";
+
+fn main() -> ExitCode {
+    // `cargo dncl ...` として呼ばれた場合は先頭に `dncl` が渡される
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_some_and(|arg| arg == "dncl") {
+        args.next();
+    }
+    let args = args.collect::<Vec<_>>();
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> eyre::Result<ExitCode> {
+    let mut positional = Vec::new();
+    let mut manifest_dir: Option<PathBuf> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--manifest-path" => {
+                let Some(path) = iter.next() else {
+                    eyre::bail!("--manifest-path requires a value");
+                };
+                manifest_dir = Some(PathBuf::from(path));
+            }
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(ExitCode::SUCCESS);
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    // `Cargo.toml` を直接指定された場合はそのディレクトリを使う
+    let manifest_dir = match manifest_dir {
+        Some(path) if path.ends_with("Cargo.toml") => path
+            .parent()
+            .map(|parent| parent.to_path_buf())
+            .unwrap_or_default(),
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    // 整形するファイルのパスは呼び出したディレクトリからの相対パスのまま扱う
+    match positional.as_slice() {
+        ["fmt", rest @ ..] => return fmt::fmt(rest, &manifest_dir),
+        ["lint", rest @ ..] => return lint::lint(rest, &manifest_dir),
+        _ => {}
+    }
+
+    // `@file` の相対パスを解決するため、マクロ展開時と同じくクレートのディレクトリで作業する
+    if !manifest_dir.as_os_str().is_empty() {
+        std::env::set_current_dir(&manifest_dir)?;
+    }
+    let manifest_dir = std::env::current_dir()?;
+
+    match positional.as_slice() {
+        ["cache", "list"] => cache::list(&manifest_dir),
+        ["cache", "prune"] => cache::prune(&manifest_dir),
+        ["cache", "verify"] => cache::verify(&manifest_dir),
+        ["cache", "purge"] => cache::purge(&manifest_dir),
+        _ => {
+            eprint!("{}", USAGE);
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dncl_trans_core::cache::CACHE_DIR_NAME;
+use dncl_trans_core::MacroInput;
+use syn::visit::{self, Visit};
+use syn::Macro;
+
+/// ソースコード中で見つかった `dncl!` 呼び出し
+pub struct Invocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub input: syn::Result<MacroInput>,
+}
+
+impl Invocation {
+    pub fn location(&self) -> String {
+        format!("{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// 探索対象外のディレクトリ
+fn is_ignored_dir(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return true;
+    };
+
+    name.starts_with('.') || name == "target" || name == CACHE_DIR_NAME
+}
+
+fn collect_rs_files(dir: &Path, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if !is_ignored_dir(&path) {
+                collect_rs_files(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+struct MacroFinder<'a> {
+    file: &'a Path,
+    found: Vec<Invocation>,
+}
+
+impl<'ast> Visit<'ast> for MacroFinder<'_> {
+    fn visit_macro(&mut self, mac: &'ast Macro) {
+        if mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "dncl")
+        {
+            self.found.push(Invocation {
+                file: self.file.to_path_buf(),
+                line: mac.path.segments[0].ident.span().start().line,
+                input: mac.parse_body::<MacroInput>(),
+            });
+        }
+
+        visit::visit_macro(self, mac);
+    }
+}
+
+/// `root` 以下の `.rs` ファイルから `dncl!` 呼び出しを探す
+///
+/// Rustとしてパースできないファイルは警告を出して読み飛ばす
+pub fn find_invocations(root: &Path) -> eyre::Result<Vec<Invocation>> {
+    let mut files = Vec::new();
+    collect_rs_files(root, &mut files)?;
+    files.sort();
+
+    let mut invocations = Vec::new();
+    for file in files {
+        let content = fs::read_to_string(&file)?;
+        let ast = match syn::parse_file(&content) {
+            Ok(ast) => ast,
+            Err(e) => {
+                eprintln!("warning: skipped {}: {}", file.display(), e);
+                continue;
+            }
+        };
+
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        let mut finder = MacroFinder {
+            file: relative,
+            found: Vec::new(),
+        };
+        finder.visit_file(&ast);
+
+        invocations.extend(finder.found);
+    }
+
+    Ok(invocations)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::MacroFinder;
+    use syn::visit::Visit;
+
+    #[test]
+    fn test_find_macros() {
+        let code = r#"
+dncl_trans::dncl!(
+    @seed = 1;
+    r"表示する(1)"
+);
+
+mod inner {
+    fn f() {
+        dncl!(表示する(2););
+        println!("not dncl");
+    }
+}
+"#;
+
+        let ast = syn::parse_file(code).unwrap();
+        let mut finder = MacroFinder {
+            file: Path::new("src/main.rs"),
+            found: Vec::new(),
+        };
+        finder.visit_file(&ast);
+
+        assert_eq!(finder.found.len(), 2);
+        assert_eq!(finder.found[0].line, 2);
+        assert_eq!(finder.found[1].line, 9);
+        assert_eq!(finder.found[0].input.as_ref().unwrap().seed, Some(1));
+    }
+}
@@ -0,0 +1,48 @@
+//! DNCLの動的型のランタイム
+//!
+//! `dncl_trans` で `@semantics = "dynamic"` を指定すると、変数をすべて [`Value`] として扱うRustコードが生成される。
+//! 整数と小数の混在、 `+` による文字列の連結、代入による配列の伸長といったDNCLの挙動をそのまま再現する
+//!
+//! ```
+//! use dncl_runtime::Value;
+//!
+//! let x = Value::from(7);
+//! let y = &x / 2;
+//! assert_eq!(y, Value::from(3.5));
+//! assert_eq!(x.div(&Value::from(2)), Value::from(3));
+//!
+//! let message = Value::from("合計: ") + &x;
+//! assert_eq!(message.to_string(), "合計: 7");
+//! ```
+
+mod ops;
+mod value;
+
+pub use value::Value;
+
+/// 「表示する」関数。引数をそのまま連結して1行で表示する
+pub fn display(values: &[Value]) {
+    println!("{}", concat(values));
+}
+
+/// 引数を連結した文字列
+pub fn concat(values: &[Value]) -> String {
+    values.iter().map(Value::to_string).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{concat, Value};
+
+    #[test]
+    fn test_concat() {
+        let values = [
+            Value::from("部員"),
+            Value::from(3),
+            Value::from("の点数は"),
+            Value::from(7.5),
+        ];
+
+        assert_eq!(concat(&values), "部員3の点数は7.5");
+    }
+}
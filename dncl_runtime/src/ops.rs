@@ -0,0 +1,274 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Not, Rem, Sub};
+
+use crate::Value;
+
+fn type_error(op: &str, a: &Value, b: &Value) -> ! {
+    panic!(
+        "{} ({}) と {} ({}) に `{}` は使えません",
+        a,
+        a.type_name(),
+        b,
+        b.type_name(),
+        op
+    )
+}
+
+/// 整数同士は整数で、それ以外の数値同士は実数で計算する
+fn numeric(
+    op: &str,
+    a: &Value,
+    b: &Value,
+    int: impl Fn(i64, i64) -> Option<i64>,
+    float: impl Fn(f64, f64) -> f64,
+) -> Value {
+    match (a, b) {
+        (Value::Int(x), Value::Int(y)) => match int(*x, *y) {
+            Some(value) => Value::Int(value),
+            None => panic!("{} {} {} はオーバーフローしました", x, op, y),
+        },
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(x), Some(y)) => Value::Float(float(x, y)),
+            _ => type_error(op, a, b),
+        },
+    }
+}
+
+fn add(a: &Value, b: &Value) -> Value {
+    match (a, b) {
+        // 文字列が含まれていれば連結する
+        (Value::Str(_), _) | (_, Value::Str(_)) => Value::Str(format!("{}{}", a, b)),
+        (Value::Array(x), Value::Array(y)) => Value::Array(x.iter().chain(y).cloned().collect()),
+        _ => numeric("+", a, b, i64::checked_add, |x, y| x + y),
+    }
+}
+
+fn sub(a: &Value, b: &Value) -> Value {
+    numeric("-", a, b, i64::checked_sub, |x, y| x - y)
+}
+
+fn mul(a: &Value, b: &Value) -> Value {
+    numeric("*", a, b, i64::checked_mul, |x, y| x * y)
+}
+
+/// `/` は整数同士でも実数の商になる
+fn div(a: &Value, b: &Value) -> Value {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(_), Some(0.0)) => panic!("0 で割ることはできません"),
+        (Some(x), Some(y)) => Value::Float(x / y),
+        _ => type_error("/", a, b),
+    }
+}
+
+fn rem(a: &Value, b: &Value) -> Value {
+    if b.as_f64() == Some(0.0) {
+        panic!("0 で割ることはできません");
+    }
+
+    numeric("%", a, b, i64::checked_rem_euclid, f64::rem_euclid)
+}
+
+impl Value {
+    /// `÷` ・ `div` による整数の商
+    pub fn div(&self, other: &Value) -> Value {
+        if other.as_f64() == Some(0.0) {
+            panic!("0 で割ることはできません");
+        }
+
+        // `%` の `rem_euclid` と合わせ、 `a == b * (a ÷ b) + a % b` が実数でも成り立つようにする
+        match numeric("÷", self, other, i64::checked_div_euclid, f64::div_euclid) {
+            Value::Float(value) => Value::Int(value as i64),
+            value => value,
+        }
+    }
+
+    /// `<` ・ `>` 等による大小比較
+    ///
+    /// 数値同士・文字列同士以外の比較はDNCLの型エラーとしてpanicする。
+    /// [`PartialOrd`] は比較できない場合に `None` を返すため、生成コードではこちらを使う
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match self.partial_cmp(other) {
+            Some(ordering) => ordering,
+            None => type_error("<", self, other),
+        }
+    }
+
+    /// `**` によるべき乗。指数が負でない整数同士は整数になる
+    pub fn pow(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Int(x), Value::Int(y)) if *y < 0 => Value::Float((*x as f64).powi(*y as i32)),
+            _ => numeric(
+                "**",
+                self,
+                other,
+                |x, y| x.checked_pow(u32::try_from(y).ok()?),
+                f64::powf,
+            ),
+        }
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($trait:ident, $method:ident, $f:ident) => {
+        impl<T: Into<Value>> $trait<T> for Value {
+            type Output = Value;
+
+            fn $method(self, other: T) -> Value {
+                $f(&self, &other.into())
+            }
+        }
+
+        impl<T: Into<Value>> $trait<T> for &Value {
+            type Output = Value;
+
+            fn $method(self, other: T) -> Value {
+                $f(self, &other.into())
+            }
+        }
+    };
+}
+
+impl_binary_op!(Add, add, add);
+impl_binary_op!(Sub, sub, sub);
+impl_binary_op!(Mul, mul, mul);
+impl_binary_op!(Div, div, div);
+impl_binary_op!(Rem, rem, rem);
+
+impl Neg for &Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        match self {
+            Value::Int(value) => Value::Int(-value),
+            Value::Float(value) => Value::Float(-value),
+            _ => panic!("{} ({}) に `-` は使えません", self, self.type_name()),
+        }
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        -&self
+    }
+}
+
+/// `not`
+impl Not for &Value {
+    type Output = Value;
+
+    fn not(self) -> Value {
+        Value::Bool(!self.truthy())
+    }
+}
+
+impl Not for Value {
+    type Output = Value;
+
+    fn not(self) -> Value {
+        !&self
+    }
+}
+
+/// 整数と実数は値として比較する
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(x), Value::Int(y)) => x == y,
+            (Value::Str(x), Value::Str(y)) => x == y,
+            (Value::Bool(x), Value::Bool(y)) => x == y,
+            (Value::Array(x), Value::Array(y)) => x == y,
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(x), Some(y)) => x == y,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// 数値同士・文字列同士のみ比較でき、それ以外は `None` になる
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(x), Value::Int(y)) => x.partial_cmp(y),
+            (Value::Str(x), Value::Str(y)) => x.partial_cmp(y),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(x), Some(y)) => x.partial_cmp(&y),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use crate::Value;
+
+    #[test]
+    fn test_arithmetic() {
+        let x = Value::from(7);
+
+        assert_eq!(&x + 1, Value::from(8));
+        assert_eq!(&x + 0.5, Value::from(7.5));
+        assert_eq!(&x / 2, Value::from(3.5));
+        assert_eq!(&x % 3, Value::from(1));
+        assert_eq!(Value::from(-7) % 3, Value::from(2));
+        assert_eq!(x.div(&Value::from(2)), Value::from(3));
+        assert_eq!(Value::from(7.5).div(&Value::from(2)), Value::from(3));
+        // 負の除数でも整数と実数で同じ規則になり、 `a == b * (a ÷ b) + a % b` が成り立つ
+        for (a, b) in [
+            (Value::from(7), Value::from(-2)),
+            (Value::from(7.0), Value::from(-2)),
+            (Value::from(-7), Value::from(-2)),
+            (Value::from(-7.0), Value::from(2.0)),
+        ] {
+            let (q, r) = (a.div(&b), &a % &b);
+            assert_eq!(&b * &q + &r, a);
+        }
+        assert_eq!(Value::from(7).div(&Value::from(-2)), Value::from(-3));
+        assert_eq!(Value::from(7.0).div(&Value::from(-2)), Value::from(-3));
+        assert_eq!(Value::from(-7).div(&Value::from(-2)), Value::from(4));
+        assert_eq!(Value::from(7.0) % -2, Value::from(1));
+        assert_eq!(Value::from(2).pow(&Value::from(10)), Value::from(1024));
+        assert_eq!(Value::from(2).pow(&Value::from(-1)), Value::from(0.5));
+        assert_eq!(-&x, Value::from(-7));
+    }
+
+    #[test]
+    fn test_concat_and_compare() {
+        assert_eq!(
+            Value::from("祇園精舎の") + "鐘の声",
+            Value::from("祇園精舎の鐘の声")
+        );
+        assert_eq!(Value::from("x = ") + 3, Value::from("x = 3"));
+
+        assert!(Value::from(3) == Value::from(3.0));
+        assert!(Value::from(2) < Value::from(2.5));
+        assert!(Value::from("a") < Value::from("b"));
+        assert_eq!(Value::from(2).compare(&Value::from(1.5)), Ordering::Greater);
+
+        // 比較できない値は `==` では比較でき、 `partial_cmp` は `None` になる
+        let (a, b) = (Value::from(vec![1]), Value::from(vec![1]));
+        assert!(a == b);
+        assert_eq!(a.partial_cmp(&b), None);
+        assert_eq!(Value::from(true).partial_cmp(&Value::from(true)), None);
+        let mut values = [Value::from(true), Value::from(1)];
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        assert_eq!(!Value::from(false), Value::from(true));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_type_error() {
+        let _ = Value::from("a") - 1;
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_compare_type_error() {
+        Value::from("a").compare(&Value::from(1));
+    }
+}
@@ -0,0 +1,278 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+
+/// DNCLの値
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Int(0)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int(value as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<&Value> for Value {
+    fn from(value: &Value) -> Self {
+        value.clone()
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(values: Vec<T>) -> Self {
+        Value::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `{外部からの入力}` の読み込み。整数・実数として読めなければ文字列とする
+impl FromStr for Value {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(value) = s.parse::<i64>() {
+            return Ok(Value::Int(value));
+        }
+        if let Ok(value) = s.parse::<f64>() {
+            return Ok(Value::Float(value));
+        }
+
+        Ok(Value::Str(s.to_string()))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Value {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "整数",
+            Value::Float(_) => "実数",
+            Value::Str(_) => "文字列",
+            Value::Bool(_) => "真偽値",
+            Value::Array(_) => "配列",
+        }
+    }
+
+    /// 数値であれば `f64` として取り出す
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// 条件式の値として評価する。真偽値以外はエラー
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            _ => panic!("{} ({}) は条件として使えません", self, self.type_name()),
+        }
+    }
+
+    /// 配列の添字として使える整数に変換する
+    pub fn as_index(&self) -> usize {
+        match self {
+            Value::Int(value) if *value >= 0 => *value as usize,
+            _ => panic!(
+                "{} ({}) は配列の添字として使えません",
+                self,
+                self.type_name()
+            ),
+        }
+    }
+
+    /// 要素数 (文字列の場合は文字数)
+    pub fn len(&self) -> Value {
+        match self {
+            Value::Array(values) => Value::from(values.len()),
+            Value::Str(value) => Value::from(value.chars().count()),
+            _ => panic!("{} ({}) の要素数は求められません", self, self.type_name()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == Value::Int(0)
+    }
+
+    /// 「整数」関数。小数点以下を切り捨てる
+    pub fn floor(&self) -> Value {
+        match self {
+            Value::Int(value) => Value::Int(*value),
+            Value::Float(value) => Value::Int(value.floor() as i64),
+            _ => panic!("{} ({}) は整数にできません", self, self.type_name()),
+        }
+    }
+
+    /// 「〜のすべての値を x にする」。多次元配列の場合はすべての次元の要素を書き換える
+    pub fn fill(&mut self, value: &Value) {
+        match self {
+            Value::Array(values) => values.iter_mut().for_each(|v| match v {
+                Value::Array(_) => v.fill(value),
+                _ => *v = value.clone(),
+            }),
+            _ => *self = value.clone(),
+        }
+    }
+
+    /// 末尾に要素を追加する
+    pub fn push(&mut self, value: Value) {
+        match self {
+            Value::Array(values) => values.push(value),
+            _ => panic!("{} ({}) には要素を追加できません", self, self.type_name()),
+        }
+    }
+}
+
+impl Index<&Value> for Value {
+    type Output = Value;
+
+    fn index(&self, index: &Value) -> &Value {
+        let i = index.as_index();
+
+        match self {
+            Value::Array(values) => values.get(i).unwrap_or_else(|| {
+                panic!("添字 {} は要素数 {} の配列の範囲外です", i, values.len())
+            }),
+            _ => panic!("{} ({}) は配列ではありません", self, self.type_name()),
+        }
+    }
+}
+
+/// 範囲外への代入では、間を `0` で埋めて配列を伸ばす
+impl IndexMut<&Value> for Value {
+    fn index_mut(&mut self, index: &Value) -> &mut Value {
+        let i = index.as_index();
+
+        match self {
+            Value::Array(values) => {
+                if values.len() <= i {
+                    values.resize(i + 1, Value::default());
+                }
+                &mut values[i]
+            }
+            _ => panic!("{} ({}) は配列ではありません", self, self.type_name()),
+        }
+    }
+}
+
+impl Index<Value> for Value {
+    type Output = Value;
+
+    fn index(&self, index: Value) -> &Value {
+        &self[&index]
+    }
+}
+
+impl IndexMut<Value> for Value {
+    fn index_mut(&mut self, index: Value) -> &mut Value {
+        &mut self[&index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Value;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::from(3).to_string(), "3");
+        assert_eq!(Value::from(3.5).to_string(), "3.5");
+        assert_eq!(Value::from(vec![1, 2, 3]).to_string(), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("42".parse::<Value>().unwrap(), Value::from(42));
+        assert_eq!("0.5".parse::<Value>().unwrap(), Value::from(0.5));
+        assert_eq!("abc".parse::<Value>().unwrap(), Value::from("abc"));
+    }
+
+    #[test]
+    fn test_index_and_fill() {
+        let mut data = Value::from(vec![10, 20]);
+        data[Value::from(3)] = Value::from(40);
+
+        assert_eq!(data.len(), Value::from(4));
+        assert_eq!(data[Value::from(2)], Value::from(0));
+
+        let mut grid = Value::from(vec![vec![1, 2], vec![3, 4]]);
+        grid.fill(&Value::from(0));
+        assert_eq!(grid, Value::from(vec![vec![0, 0], vec![0, 0]]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_range() {
+        let data = Value::from(vec![10, 20]);
+        let _ = &data[Value::from(2)];
+    }
+}
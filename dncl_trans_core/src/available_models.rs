@@ -0,0 +1,293 @@
+use crate::cache::{ensure_dir, hash_content};
+use crate::config::Config;
+use crate::datetime;
+use crate::macro_::IntoSynRes;
+use proc_macro2::Span;
+use reqwest::blocking::Client;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use syn::LitStr;
+use toml::value::Datetime;
+
+pub(crate) const DEFAULT_MODEL: &str = "gpt-4o";
+
+fn get_available_gpt_models(api_key: &str, base_url: &str) -> eyre::Result<Vec<String>> {
+    let api_key_field = format!("Bearer {}", api_key);
+
+    let body: Value = Client::new()
+        .get(format!("{}/models", base_url))
+        .header("Authorization", api_key_field.as_str())
+        .send()?
+        .json()?;
+
+    let model_array = match &body["data"] {
+        Value::Array(array) => array,
+        _ => eyre::bail!("Unexpected response: {:?}", body),
+    };
+
+    let model_names = model_array
+        .iter()
+        .map(|value| match &value["id"] {
+            Value::String(s) => Ok(s.clone()),
+            _ => eyre::bail!("Unexpected response: {:?}", value),
+        })
+        .collect::<eyre::Result<Vec<String>>>()?;
+
+    Ok(model_names)
+}
+
+/// モデル一覧キャッシュの有効期限 (時間) を指定する環境変数
+const MODELS_TTL_ENV: &str = "DNCL_MODELS_TTL_HOURS";
+
+/// モデル一覧キャッシュの有効期限のデフォルト (1週間)
+const DEFAULT_MODELS_TTL_HOURS: u64 = 24 * 7;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ModelsCache {
+    /// 取得日時。古いキャッシュには存在しないため、その場合は期限切れとして扱う
+    fetched_at: Option<Datetime>,
+    available_models: Vec<String>,
+}
+
+impl ModelsCache {
+    fn is_fresh(&self, ttl_secs: u64) -> bool {
+        let Some(fetched_at) = self.fetched_at.as_ref().and_then(datetime::to_unix) else {
+            return false;
+        };
+
+        datetime::unix_now().saturating_sub(fetched_at) < ttl_secs
+    }
+}
+
+/// モデル一覧キャッシュの有効期限 (秒)
+///
+/// 環境変数、設定ファイルの `models_ttl_hours` 、デフォルトの順に決まる。大きすぎる値は無期限として扱う
+fn models_ttl_secs(config: &Config) -> eyre::Result<u64> {
+    let hours = match env::var(MODELS_TTL_ENV) {
+        Ok(hours) => hours
+            .parse::<u64>()
+            .map_err(|e| eyre::eyre!("{} must be an integer: {}", MODELS_TTL_ENV, e))?,
+        Err(_) => config.models_ttl_hours.unwrap_or(DEFAULT_MODELS_TTL_HOURS),
+    };
+
+    Ok(hours.saturating_mul(60 * 60))
+}
+
+/// モデル一覧の取得結果
+struct AvailableModels {
+    models: Vec<String>,
+    /// 今回APIからの取得を試みたかどうか
+    fetched: bool,
+}
+
+// モデル一覧は基本キャッシュしておき、存在しないか期限切れの場合だけAPIを叩く
+// `refresh` が `true` の場合はキャッシュを無視して取得し直す
+fn available_gpt_models(
+    api_key: &str,
+    config: &Config,
+    refresh: bool,
+) -> eyre::Result<AvailableModels> {
+    // OpenAI以外のAPIを使う場合はベースURLごとにキャッシュする
+    let file_name = match &config.base_url {
+        None => "available_models.toml".to_string(),
+        Some(base_url) => format!("available_models_{}.toml", hash_content(base_url)),
+    };
+    let cached_models_file_name = ensure_dir(config.cache_dir())?.join(file_name);
+
+    let mut stale = None;
+
+    match fs::read_to_string(&cached_models_file_name) {
+        Ok(content) => {
+            let cache: ModelsCache = toml::from_str(&content)?;
+
+            if !refresh && cache.is_fresh(models_ttl_secs(config)?) {
+                return Ok(AvailableModels {
+                    models: cache.available_models,
+                    fetched: false,
+                });
+            }
+
+            stale = Some(cache.available_models);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let available_models = match (get_available_gpt_models(api_key, config.base_url()), stale) {
+        (Ok(models), _) => models,
+        // 取得に失敗した場合は期限切れのキャッシュで代用する
+        (Err(_), Some(stale)) => {
+            return Ok(AvailableModels {
+                models: stale,
+                fetched: true,
+            })
+        }
+        (Err(e), None) => return Err(e),
+    };
+
+    let cache = ModelsCache {
+        fetched_at: Some(datetime::now_datetime()),
+        available_models: available_models.clone(),
+    };
+    // tomlとして保存するために変換
+    let cache = toml::to_string(&cache)?;
+    // 結果を保存
+    fs::write(&cached_models_file_name, cache)?;
+
+    Ok(AvailableModels {
+        models: available_models,
+        fetched: true,
+    })
+}
+
+/// `@model` の値 (省略時は設定ファイルの値) を実際のモデル名に解決する。どちらもなければデフォルトのモデル
+pub(crate) fn resolve_model(model_lit: Option<&LitStr>, config: &Config) -> String {
+    match model_lit
+        .map(LitStr::value)
+        .or_else(|| config.model.clone())
+    {
+        Some(model) => config.resolve_model(&model).to_string(),
+        None => DEFAULT_MODEL.to_string(),
+    }
+}
+
+/// 編集距離 (レーベンシュタイン距離)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+
+    prev[b.len()]
+}
+
+/// 似た名前の候補を近い順に最大3つ返す
+fn suggestions<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let threshold = (name.chars().count() / 3).max(2);
+
+    let mut scored = candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect::<Vec<_>>();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+
+    scored
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// モデル名を解決し、 `check_model` が `true` の場合は利用可能か確認する
+pub fn check_available(
+    api_key: &str,
+    model_lit: Option<&LitStr>,
+    config: &Config,
+    check_model: bool,
+) -> syn::Result<String> {
+    let span = model_lit.map(LitStr::span).unwrap_or_else(Span::call_site);
+    let model_name = resolve_model(model_lit, config);
+
+    // モデル一覧を提供しないプロバイダやデプロイ名を使う場合はチェックしない
+    if !check_model {
+        return Ok(model_name);
+    }
+
+    let mut available = available_gpt_models(api_key, config, false).into_syn(span)?;
+
+    // キャッシュ取得後に公開されたモデルかもしれないので、一度だけ取得し直す
+    if !available.models.contains(&model_name) && !available.fetched {
+        available = available_gpt_models(api_key, config, true).into_syn(span)?;
+    }
+
+    if !available.models.contains(&model_name) {
+        let candidates = available
+            .models
+            .iter()
+            .chain(config.aliases.keys())
+            .map(String::as_str);
+
+        let message = match suggestions(&model_name, candidates).as_slice() {
+            [] => format!("Model {} is not available", model_name),
+            names => format!(
+                "Model {} is not available. Did you mean {}?",
+                model_name,
+                names.join(", ")
+            ),
+        };
+
+        return Err(syn::Error::new(span, message));
+    }
+
+    Ok(model_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{edit_distance, models_ttl_secs, suggestions, ModelsCache, MODELS_TTL_ENV};
+    use crate::config::Config;
+    use crate::datetime;
+
+    #[test]
+    fn test_models_cache_freshness() {
+        let cache = |fetched_at| ModelsCache {
+            fetched_at,
+            available_models: vec!["gpt-4o".to_string()],
+        };
+        let hour = 60 * 60;
+
+        assert!(cache(Some(datetime::now_datetime())).is_fresh(hour));
+        assert!(!cache(Some(datetime::from_unix(datetime::unix_now() - 2 * hour))).is_fresh(hour));
+        // 取得日時のない古いキャッシュは期限切れ扱い
+        assert!(!cache(None).is_fresh(hour));
+    }
+
+    #[test]
+    fn test_models_ttl_secs() {
+        // 環境変数が設定されている場合はそちらが優先されるため確認しない
+        if std::env::var(MODELS_TTL_ENV).is_ok() {
+            return;
+        }
+        let config = |models_ttl_hours| Config {
+            models_ttl_hours,
+            ..Config::default()
+        };
+
+        assert_eq!(models_ttl_secs(&config(None)).unwrap(), 24 * 7 * 60 * 60);
+        assert_eq!(models_ttl_secs(&config(Some(2))).unwrap(), 2 * 60 * 60);
+        // 大きすぎる値でもオーバーフローしない
+        assert_eq!(models_ttl_secs(&config(Some(u64::MAX))).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("gpt-4o", "gpt-4o"), 0);
+        assert_eq!(edit_distance("gpt4o", "gpt-4o"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let models = ["gpt-4o", "gpt-4o-mini", "o1-preview", "gpt-3.5-turbo"];
+
+        assert_eq!(suggestions("gpt4o", models.into_iter()), vec!["gpt-4o"]);
+        assert_eq!(
+            suggestions("gpt-4o-mni", models.into_iter()),
+            vec!["gpt-4o-mini"]
+        );
+        assert_eq!(
+            suggestions("o1-preveiw", models.into_iter()),
+            vec!["o1-preview"]
+        );
+        assert!(suggestions("claude", models.into_iter()).is_empty());
+    }
+}
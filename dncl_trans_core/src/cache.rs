@@ -0,0 +1,329 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use toml::value::Datetime;
+
+use crate::candidate::{Generated, Rejected};
+use crate::datetime::now_datetime;
+use crate::query::{QuerySetting, Usage};
+use crate::sampling::Sampling;
+
+/// キャッシュを保存するディレクトリ名
+pub const CACHE_DIR_NAME: &str = "gpt_responses";
+
+/// `@regenerate` で置き換えられた古いキャッシュを保存するディレクトリ名 ( `gpt_responses` 内)
+pub const HISTORY_DIR_NAME: &str = "history";
+
+/// キャッシュファイル1つ分の内容
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct CachedContent {
+    pub model: String,
+    pub seed: i64,
+    pub max_completion_tokens: Option<u32>,
+    // 以下は後から追加した項目のため、古いキャッシュには存在しないことがある
+    pub created_at: Option<Datetime>,
+    pub system_fingerprint: Option<String>,
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Sampling::is_empty")]
+    pub sampling: Sampling,
+    pub dncl: Option<String>,
+    pub rust: Option<String>,
+    /// `@regenerate = true` により再生成されたキャッシュかどうか
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub regenerated: bool,
+    /// 採用した返答がチェックを通らなかった場合の理由
+    pub check_error: Option<String>,
+    pub response: String,
+    /// 採用されなかった候補とその理由
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<Rejected>,
+}
+
+impl CachedContent {
+    /// コンパイルに使われるRustコード
+    pub fn rust_code(&self) -> String {
+        match &self.rust {
+            Some(rust) => rust.clone(),
+            None => crate::extract_rust_code(&self.response),
+        }
+    }
+}
+
+/// ディレクトリが存在しない場合は作成する
+pub(crate) fn ensure_dir(dir: PathBuf) -> eyre::Result<PathBuf> {
+    if !fs::exists(&dir)? {
+        fs::create_dir_all(&dir)?;
+    }
+
+    Ok(dir)
+}
+
+pub(crate) fn cache_file_name(setting: &QuerySetting, content: &str) -> String {
+    format!("cache_{}.toml", hash_content(&(setting, content)))
+}
+
+fn get_cache_file_path(setting: &QuerySetting, content: &str) -> eyre::Result<PathBuf> {
+    Ok(ensure_dir(setting.cache_dir.clone())?.join(cache_file_name(setting, content)))
+}
+
+/// 内容が変わる場合だけファイルに書き込む
+///
+/// コミットされたキャッシュに通常のビルドで差分が出ないようにするため。書き込んだかどうかを返す
+fn write_if_changed(path: &Path, contents: &str) -> eyre::Result<bool> {
+    match fs::read_to_string(path) {
+        Ok(current) if current == contents => return Ok(false),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    fs::write(path, contents)?;
+
+    Ok(true)
+}
+
+/// `@pin` で指定されたキャッシュファイル名
+///
+/// `"cache_123.toml"` ・ `"cache_123"` ・ `"123"` のいずれの形式でも指定できる
+pub fn pinned_file_name(pin: &str) -> String {
+    let name = pin.trim_end_matches(".toml");
+
+    match name.starts_with("cache_") {
+        true => format!("{}.toml", name),
+        false => format!("cache_{}.toml", name),
+    }
+}
+
+/// `@pin` で指定されたキャッシュを読み込む。履歴ディレクトリも探す
+pub(crate) fn load_pinned(cache_dir: &Path, pin: &str) -> eyre::Result<CachedContent> {
+    let file_name = pinned_file_name(pin);
+
+    for path in [
+        cache_dir.join(&file_name),
+        cache_dir.join(HISTORY_DIR_NAME).join(&file_name),
+    ] {
+        if fs::exists(&path)? {
+            return read_entry(&path);
+        }
+    }
+
+    eyre::bail!("Pinned cache {} is not found", file_name)
+}
+
+/// キャッシュディレクトリ内のキャッシュファイル ( `cache_*.toml` ) 一覧
+pub fn entry_paths(cache_dir: &Path) -> eyre::Result<Vec<PathBuf>> {
+    if !fs::exists(cache_dir)? {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = fs::read_dir(cache_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.retain(|path| {
+        let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+        let is_cache = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("cache_"));
+
+        is_toml && is_cache
+    });
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// キャッシュファイルを読み込む
+pub fn read_entry(path: &Path) -> eyre::Result<CachedContent> {
+    let content = fs::read_to_string(path)?;
+
+    Ok(toml::from_str(&content)?)
+}
+
+pub fn hash_content<H: Hash>(key: &H) -> i64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % (i64::MAX as u64)) as _
+}
+
+/// Rustとしてパースできるならprettypleaseで整形する
+fn pretty_rust(code: &str) -> String {
+    match syn::parse_file(code) {
+        Ok(file) => prettyplease::unparse(&file),
+        Err(_) => code.to_string(),
+    }
+}
+
+impl QuerySetting {
+    pub fn load_cache(&self, content: &str) -> eyre::Result<Option<CachedContent>> {
+        let cache_file = get_cache_file_path(self, content)?;
+
+        // キャッシュを読み込む
+        let response = fs::read_to_string(cache_file);
+
+        match response {
+            Ok(response) => {
+                let response: CachedContent = toml::from_str(&response)?;
+                Ok(Some(response))
+            }
+            // 存在しない場合
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 既存のキャッシュを履歴ディレクトリへ移動する
+    ///
+    /// 履歴は `history/cache_<ハッシュ値>_<連番>.toml` として保存される
+    pub fn archive_cache(&self, content: &str) -> eyre::Result<()> {
+        let cache_file = get_cache_file_path(self, content)?;
+        if !fs::exists(&cache_file)? {
+            return Ok(());
+        }
+
+        let history_dir = ensure_dir(self.cache_dir.join(HISTORY_DIR_NAME))?;
+
+        let stem = cache_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let history_file = (1..)
+            .map(|n| history_dir.join(format!("{}_{}.toml", stem, n)))
+            .find(|path| !path.exists())
+            .expect("history file name is exhausted");
+
+        fs::rename(&cache_file, history_file)?;
+
+        Ok(())
+    }
+
+    /// 再生成済みの印を付け外しして保存し直す
+    ///
+    /// 印が既に指定の状態であればファイルには触れない
+    pub fn mark_regenerated(
+        &self,
+        content: &str,
+        mut cache: CachedContent,
+        regenerated: bool,
+    ) -> eyre::Result<()> {
+        if cache.regenerated == regenerated {
+            return Ok(());
+        }
+
+        let cache_file = get_cache_file_path(self, content)?;
+        cache.regenerated = regenerated;
+
+        write_if_changed(&cache_file, &toml::to_string_pretty(&cache)?)?;
+
+        Ok(())
+    }
+
+    /// 返答をキャッシュへ保存する
+    ///
+    /// `emit_rs` が `true` の場合は抽出したRustコードを同名の `.rs` ファイルにも書き出す
+    pub fn save_cache(
+        &self,
+        content: &str,
+        dncl: &str,
+        generated: &Generated,
+        emit_rs: bool,
+        regenerated: bool,
+    ) -> eyre::Result<()> {
+        let cache_file = get_cache_file_path(self, content)?;
+
+        let QuerySetting {
+            model,
+            seed,
+            max_completion_tokens,
+            ..
+        } = self;
+
+        let rust = pretty_rust(&crate::extract_rust_code(&generated.response));
+
+        let contents = CachedContent {
+            model: model.to_string(),
+            seed: *seed,
+            max_completion_tokens: *max_completion_tokens,
+            created_at: Some(now_datetime()),
+            system_fingerprint: generated.system_fingerprint.clone(),
+            usage: generated.usage.clone(),
+            sampling: self.sampling.clone(),
+            dncl: Some(dncl.to_string()),
+            rust: Some(rust.clone()),
+            regenerated,
+            check_error: generated.check_error.clone(),
+            response: generated.response.clone(),
+            rejected: generated.rejected.clone(),
+        };
+
+        // tomlとして保存するために変換 (差分を読みやすくするため複数行文字列を使う)
+        let contents = toml::to_string_pretty(&contents)?;
+
+        // 結果を保存
+        write_if_changed(&cache_file, &contents)?;
+
+        if emit_rs {
+            write_if_changed(&cache_file.with_extension("rs"), &rust)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pinned_file_name, pretty_rust, write_if_changed, CachedContent};
+
+    #[test]
+    fn test_pretty_rust() {
+        let code = "fn main(){let x=1;println!(\"{}\",x);}";
+        let expected = r#"fn main() {
+    let x = 1;
+    println!("{}", x);
+}
+"#;
+
+        assert_eq!(pretty_rust(code), expected);
+        // パースできない場合はそのまま
+        assert_eq!(pretty_rust("fn main( {"), "fn main( {");
+    }
+
+    #[test]
+    fn test_load_old_cache() {
+        let old = r#"
+model = "gpt-4o"
+seed = 123456
+response = "fn main() {}"
+"#;
+
+        let cache: CachedContent = toml::from_str(old).unwrap();
+        assert_eq!(cache.response, "fn main() {}");
+        assert!(cache.dncl.is_none());
+        assert!(!cache.regenerated);
+    }
+
+    #[test]
+    fn test_pinned_file_name() {
+        assert_eq!(pinned_file_name("123"), "cache_123.toml");
+        assert_eq!(pinned_file_name("cache_123"), "cache_123.toml");
+        assert_eq!(pinned_file_name("cache_123.toml"), "cache_123.toml");
+        assert_eq!(pinned_file_name("cache_123_2"), "cache_123_2.toml");
+    }
+
+    #[test]
+    fn test_write_if_changed() {
+        let dir = std::env::temp_dir().join(format!("dncl_trans_write_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache_1.toml");
+
+        assert!(write_if_changed(&path, "a = 1\n").unwrap());
+        // 同じ内容では書き込まない
+        assert!(!write_if_changed(&path, "a = 1\n").unwrap());
+        assert!(write_if_changed(&path, "a = 2\n").unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 2\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CACHE_DIR_NAME;
+use crate::sampling::{ReasoningEffort, Sampling};
+
+/// プロジェクト設定ファイル名 ( `CARGO_MANIFEST_DIR` 直下に置く)
+pub const CONFIG_FILE_NAME: &str = "dncl_trans.toml";
+
+/// OpenAI APIのベースURL
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// APIキーを読み込む環境変数名のデフォルト
+pub const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// APIの提供元
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum Provider {
+    /// OpenAI API
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// OpenAI互換API (ローカルLLMや各種ゲートウェイ) 。 `base_url` の指定が必須
+    #[serde(rename = "openai-compatible")]
+    OpenAiCompatible,
+}
+
+/// 生成するRustコードでのDNCLの値の扱い
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Semantics {
+    /// 使われ方から推測したRustの型を使う
+    #[default]
+    Static,
+    /// `dncl_runtime::Value` による動的型を使う
+    Dynamic,
+    /// DNCLソースコードから推論した型を指示し、推論できなかった変数のみ動的型を使う
+    Inferred,
+}
+
+impl std::str::FromStr for Semantics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "static" => Ok(Self::Static),
+            "dynamic" => Ok(Self::Dynamic),
+            "inferred" => Ok(Self::Inferred),
+            _ => Err(format!(
+                "semantics must be one of \"static\", \"dynamic\" or \"inferred\", but got \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+/// DNCLの文法
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    /// 令和7年度以降の大学入学共通テスト「共通テスト手順記述標準言語」
+    #[default]
+    R7,
+    /// 2021年度以前の大学入試センター試験の「DNCL」 ( `←` による代入、「…を実行する」「…を繰り返す」で閉じる制御文)
+    Legacy,
+}
+
+impl std::str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r7" => Ok(Self::R7),
+            "legacy" => Ok(Self::Legacy),
+            _ => Err(format!(
+                "dialect must be one of \"r7\" or \"legacy\", but got \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+/// プロジェクト設定
+///
+/// `dncl_trans.toml` 、なければ `Cargo.toml` の `[package.metadata.dncl_trans]` から読み込む。
+/// マクロ側で同名のオプションが指定された場合はそちらが優先される
+///
+/// ```toml
+/// provider = "openai"
+/// model = "fast"
+/// seed = 123456
+/// offline = false
+///
+/// [aliases]
+/// fast = "gpt-4o-mini"
+/// smart = "o1-preview"
+/// ```
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub provider: Provider,
+    /// `@model` のデフォルト値 (別名も可)
+    pub model: Option<String>,
+    /// `@seed` のデフォルト値
+    pub seed: Option<i64>,
+    /// `@max_completion_tokens` のデフォルト値
+    pub max_completion_tokens: Option<u32>,
+    /// `@temperature` のデフォルト値
+    pub temperature: Option<f64>,
+    /// `@top_p` のデフォルト値
+    pub top_p: Option<f64>,
+    /// `@reasoning_effort` のデフォルト値
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// `@stop` のデフォルト値
+    pub stop: Option<Vec<String>>,
+    /// `@n` のデフォルト値
+    pub n: Option<u32>,
+    /// `@best_of` のデフォルト値
+    pub best_of: Option<u32>,
+    /// APIのベースURL (例: `http://localhost:11434/v1` )
+    pub base_url: Option<String>,
+    /// APIキーを読み込む環境変数名
+    pub api_key_env: Option<String>,
+    /// `@check_model` のデフォルト値
+    pub check_model: Option<bool>,
+    /// キャッシュディレクトリ ( `Cargo.toml` のあるディレクトリからの相対パス)
+    pub cache_dir: Option<PathBuf>,
+    /// モデル一覧のキャッシュの有効期限 (時間) 。環境変数 `DNCL_MODELS_TTL_HOURS` があればそちらを優先する
+    pub models_ttl_hours: Option<u64>,
+    /// `true` の場合はAPIを叩かず、キャッシュがなければエラーにする
+    pub offline: bool,
+    /// `@emit_rs` のデフォルト値
+    pub emit_rs: bool,
+    /// DNCLの仕様に加えてモデルに渡す指示
+    pub prompt_extra: Option<String>,
+    /// 組み込みのDNCLの仕様の代わりに使う仕様ファイル ( `Cargo.toml` のあるディレクトリからの相対パス)
+    pub spec_file: Option<PathBuf>,
+    /// `@index_base` のデフォルト値
+    pub index_base: Option<u32>,
+    /// `@semantics` のデフォルト値
+    pub semantics: Semantics,
+    /// `@dialect` のデフォルト値
+    pub dialect: Dialect,
+    /// モデル名の別名
+    pub aliases: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub(crate) manifest_dir: PathBuf,
+}
+
+impl Config {
+    /// `manifest_dir` 直下の設定を読み込む。設定がない場合はデフォルト値
+    pub fn load(manifest_dir: &Path) -> eyre::Result<Self> {
+        let path = manifest_dir.join(CONFIG_FILE_NAME);
+
+        let mut config: Self = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| eyre::eyre!("failed to parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Self::load_cargo_metadata(manifest_dir)?.unwrap_or_default()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if config.provider == Provider::OpenAiCompatible && config.base_url.is_none() {
+            eyre::bail!("`base_url` is required for provider \"openai-compatible\"");
+        }
+
+        config.manifest_dir = manifest_dir.to_path_buf();
+
+        Ok(config)
+    }
+
+    /// `Cargo.toml` の `[package.metadata.dncl_trans]` を読み込む
+    fn load_cargo_metadata(manifest_dir: &Path) -> eyre::Result<Option<Self>> {
+        let path = manifest_dir.join("Cargo.toml");
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let manifest: toml::Table = toml::from_str(&content)?;
+        let Some(metadata) = manifest
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("dncl_trans"))
+        else {
+            return Ok(None);
+        };
+
+        let config = metadata.clone().try_into().map_err(|e| {
+            eyre::eyre!(
+                "failed to parse [package.metadata.dncl_trans] in {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+
+        Ok(Some(config))
+    }
+
+    /// マクロ展開中のクレートの設定を読み込む
+    pub(crate) fn load_manifest() -> eyre::Result<Self> {
+        let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+
+        Self::load(&manifest_dir)
+    }
+
+    /// 別名であれば実際のモデル名に解決する
+    pub fn resolve_model<'a>(&'a self, model: &'a str) -> &'a str {
+        self.aliases.get(model).map(String::as_str).unwrap_or(model)
+    }
+
+    /// キャッシュディレクトリ
+    pub fn cache_dir(&self) -> PathBuf {
+        let cache_dir = self
+            .cache_dir
+            .as_deref()
+            .unwrap_or(Path::new(CACHE_DIR_NAME));
+
+        self.manifest_dir.join(cache_dir)
+    }
+
+    /// `spec_file` が指定されていればその内容
+    pub fn spec(&self) -> eyre::Result<Option<String>> {
+        let Some(spec_file) = &self.spec_file else {
+            return Ok(None);
+        };
+
+        let path = self.manifest_dir.join(spec_file);
+        let spec = fs::read_to_string(&path)
+            .map_err(|e| eyre::eyre!("failed to read spec file {}: {}", path.display(), e))?;
+
+        Ok(Some(spec))
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url
+            .as_deref()
+            .unwrap_or(DEFAULT_BASE_URL)
+            .trim_end_matches('/')
+    }
+
+    pub fn api_key_env(&self) -> &str {
+        self.api_key_env.as_deref().unwrap_or(DEFAULT_API_KEY_ENV)
+    }
+
+    /// サンプリングパラメータのデフォルト値
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            reasoning_effort: self.reasoning_effort,
+            stop: self.stop.clone(),
+            n: self.n,
+        }
+    }
+
+    /// OpenAI互換APIはモデル一覧を提供しないことが多いため、デフォルトではチェックしない
+    pub fn check_model(&self) -> bool {
+        self.check_model
+            .unwrap_or(self.provider == Provider::OpenAi)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{Config, Provider};
+
+    #[test]
+    fn test_resolve_model() {
+        let config: Config = toml::from_str(
+            r#"
+[aliases]
+fast = "gpt-4o-mini"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.resolve_model("fast"), "gpt-4o-mini");
+        assert_eq!(config.resolve_model("gpt-4o"), "gpt-4o");
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(toml::from_str::<Config>("alias = 1").is_err());
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+provider = "openai-compatible"
+base_url = "http://localhost:11434/v1/"
+cache_dir = "dncl_cache"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.provider, Provider::OpenAiCompatible);
+        assert_eq!(config.base_url(), "http://localhost:11434/v1");
+        assert_eq!(config.api_key_env(), "OPENAI_API_KEY");
+        assert!(!config.check_model());
+        assert_eq!(config.cache_dir(), Path::new("dncl_cache"));
+
+        let config = Config::default();
+        assert_eq!(config.base_url(), "https://api.openai.com/v1");
+        assert!(config.check_model());
+        assert_eq!(config.cache_dir(), Path::new("gpt_responses"));
+    }
+
+    #[test]
+    fn test_load_cargo_metadata() {
+        let dir = std::env::temp_dir().join(format!("dncl_trans_config_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            r#"
+[package]
+name = "hoge"
+
+[package.metadata.dncl_trans]
+model = "gpt-4o-mini"
+seed = 42
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(config.seed, Some(42));
+        assert_eq!(config.cache_dir(), dir.join("gpt_responses"));
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use toml::value::{Date, Datetime, Offset, Time};
+
+/// 現在のUNIX時間 (秒)
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// 現在時刻をUTCのTOML日時に変換する
+pub fn now_datetime() -> Datetime {
+    from_unix(unix_now())
+}
+
+/// UNIX時間をUTCのTOML日時に変換する
+pub fn from_unix(secs: u64) -> Datetime {
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // 1970-01-01 からの日数をグレゴリオ暦に変換 (Howard Hinnant の civil_from_days)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+
+    Datetime {
+        date: Some(Date { year, month, day }),
+        time: Some(Time {
+            hour: (rem / 3600) as u8,
+            minute: (rem % 3600 / 60) as u8,
+            second: (rem % 60) as u8,
+            nanosecond: 0,
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+/// TOML日時をUNIX時間に変換する。日付部分がない場合は `None`
+pub fn to_unix(datetime: &Datetime) -> Option<u64> {
+    let Date { year, month, day } = datetime.date?;
+    let (hour, minute, second) = datetime
+        .time
+        .map(|t| (t.hour, t.minute, t.second))
+        .unwrap_or_default();
+    let offset_minutes = match datetime.offset {
+        Some(Offset::Custom { minutes }) => minutes as i64,
+        _ => 0,
+    };
+
+    // グレゴリオ暦を 1970-01-01 からの日数に変換 (Howard Hinnant の days_from_civil)
+    let (year, month) = (year as i64, month as i64);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64
+        - offset_minutes * 60;
+
+    u64::try_from(secs).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_unix, now_datetime, to_unix};
+
+    #[test]
+    fn test_now_datetime() {
+        let now = now_datetime().to_string();
+
+        // 例: 2025-01-23T04:56:07Z
+        assert_eq!(now.len(), 20);
+        assert!(now.ends_with('Z'));
+    }
+
+    #[test]
+    fn test_unix_roundtrip() {
+        assert_eq!(from_unix(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(from_unix(951782400).to_string(), "2000-02-29T00:00:00Z");
+
+        for secs in [0, 951782400, 1737600000, 4102444799] {
+            assert_eq!(to_unix(&from_unix(secs)), Some(secs));
+        }
+
+        let jst = "2025-01-23T09:00:00+09:00".parse().unwrap();
+        assert_eq!(
+            to_unix(&jst),
+            to_unix(&"2025-01-23T00:00:00Z".parse().unwrap())
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! 生成コードが使う外部クレートの検出
+//!
+//! `use` やパスの先頭からクレート名を集め、ユーザーの `Cargo.toml` に書かれた依存と比較する
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use syn::visit::{self, Visit};
+use syn::{Item, ItemExternCrate, ItemUse, Macro, Path as SynPath, UseTree};
+
+use crate::candidate::visit_macro_args;
+
+/// `Cargo.toml` に書かなくても使えるクレート
+const BUILTIN_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// パスの先頭に来てもクレートではない名前
+const NON_CRATE_ROOTS: &[&str] = &[
+    "crate", "self", "super", "Self", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+    "u32", "u64", "u128", "usize", "f32", "f64", "bool", "char", "str",
+];
+
+/// パスの先頭の名前と、 `use` や項目の定義 ( `mod` ・ `enum` ・ `fn` 等) でローカルに導入された名前を集める
+#[derive(Default)]
+struct CrateCollector {
+    roots: BTreeSet<String>,
+    locals: BTreeSet<String>,
+}
+
+impl CrateCollector {
+    /// `use` で導入される名前を集める
+    fn collect_imported(&mut self, tree: &UseTree, parent: Option<&syn::Ident>) {
+        match tree {
+            UseTree::Path(path) => self.collect_imported(&path.tree, Some(&path.ident)),
+            UseTree::Name(name) if name.ident == "self" => {
+                self.locals.extend(parent.map(ToString::to_string));
+            }
+            UseTree::Name(name) => {
+                self.locals.insert(name.ident.to_string());
+            }
+            UseTree::Rename(rename) => {
+                self.locals.insert(rename.rename.to_string());
+            }
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.collect_imported(tree, parent);
+                }
+            }
+            UseTree::Glob(_) => {}
+        }
+    }
+}
+
+/// `use` の先頭の名前
+fn use_roots(tree: &UseTree) -> Vec<String> {
+    match tree {
+        UseTree::Path(path) => vec![path.ident.to_string()],
+        UseTree::Name(name) => vec![name.ident.to_string()],
+        UseTree::Rename(rename) => vec![rename.ident.to_string()],
+        UseTree::Group(group) => group.items.iter().flat_map(use_roots).collect(),
+        UseTree::Glob(_) => Vec::new(),
+    }
+}
+
+impl<'ast> Visit<'ast> for CrateCollector {
+    fn visit_item_use(&mut self, node: &'ast ItemUse) {
+        self.roots.extend(use_roots(&node.tree));
+        self.collect_imported(&node.tree, None);
+    }
+
+    fn visit_item_extern_crate(&mut self, node: &'ast ItemExternCrate) {
+        self.roots.insert(node.ident.to_string());
+    }
+
+    fn visit_item(&mut self, node: &'ast Item) {
+        // `use Direction::*;` のように生成コード中で定義した項目から `use` することがある
+        let ident = match node {
+            Item::Mod(item) => Some(&item.ident),
+            Item::Enum(item) => Some(&item.ident),
+            Item::Struct(item) => Some(&item.ident),
+            Item::Union(item) => Some(&item.ident),
+            Item::Trait(item) => Some(&item.ident),
+            Item::Type(item) => Some(&item.ident),
+            Item::Fn(item) => Some(&item.sig.ident),
+            Item::Const(item) => Some(&item.ident),
+            Item::Static(item) => Some(&item.ident),
+            Item::Macro(item) => item.ident.as_ref(),
+            _ => None,
+        };
+        self.locals.extend(ident.map(ToString::to_string));
+
+        visit::visit_item(self, node);
+    }
+
+    fn visit_path(&mut self, node: &'ast SynPath) {
+        // `rand::random()` のような2つ以上のセグメントからなるパスの先頭だけを見る
+        // (型やトレイト、列挙子は大文字で始まるので除く)
+        if node.segments.len() >= 2 || node.leading_colon.is_some() {
+            let root = node.segments[0].ident.to_string();
+            if root.starts_with(|c: char| c.is_lowercase()) {
+                self.roots.insert(root);
+            }
+        }
+
+        visit::visit_path(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        visit::visit_macro(self, node);
+        visit_macro_args(self, node);
+    }
+}
+
+/// 生成コードが使っている外部クレート (名前順)
+pub(crate) fn used_crates(file: &syn::File) -> Vec<String> {
+    let mut collector = CrateCollector::default();
+    collector.visit_file(file);
+
+    collector
+        .roots
+        .into_iter()
+        .filter(|root| !collector.locals.contains(root))
+        .filter(|root| !BUILTIN_CRATES.contains(&root.as_str()))
+        .filter(|root| !NON_CRATE_ROOTS.contains(&root.as_str()))
+        .collect()
+}
+
+/// `Cargo.toml` に書かれている依存クレート (と自身) の、コード中で使われる名前
+///
+/// `[dependencies]` 、 `[dev-dependencies]` 、 `[target.*.dependencies]` 等を対象とする
+fn declared_crates(manifest: &toml::Table) -> BTreeSet<String> {
+    let tables = ["dependencies", "dev-dependencies", "build-dependencies"];
+    let dependencies = |table: &toml::Table| {
+        tables
+            .iter()
+            .filter_map(|name| table.get(*name).and_then(toml::Value::as_table))
+            .flat_map(|deps| deps.keys().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+
+    let mut crates = dependencies(manifest);
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        for target in targets.values().filter_map(toml::Value::as_table) {
+            crates.extend(dependencies(target));
+        }
+    }
+    for key in ["package", "lib"] {
+        if let Some(name) = manifest
+            .get(key)
+            .and_then(|table| table.get("name"))
+            .and_then(toml::Value::as_str)
+        {
+            crates.push(name.to_string());
+        }
+    }
+
+    crates.iter().map(|name| name.replace('-', "_")).collect()
+}
+
+/// `manifest_dir` の `Cargo.toml` に書かれている依存クレート
+///
+/// `Cargo.toml` がない場合は `None`
+pub(crate) fn manifest_crates(manifest_dir: &Path) -> eyre::Result<Option<BTreeSet<String>>> {
+    let path = manifest_dir.join("Cargo.toml");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let manifest: toml::Table = toml::from_str(&content)
+        .map_err(|e| eyre::eyre!("failed to parse {}: {}", path.display(), e))?;
+
+    Ok(Some(declared_crates(&manifest)))
+}
+
+/// 生成コードが使っているのに `Cargo.toml` にないクレート
+///
+/// `Cargo.toml` がない場合は確認しない
+pub(crate) fn missing_crates(code: &str, manifest_dir: &Path) -> eyre::Result<Vec<String>> {
+    let Ok(file) = syn::parse_file(code) else {
+        return Ok(Vec::new());
+    };
+    let Some(declared) = manifest_crates(manifest_dir)? else {
+        return Ok(Vec::new());
+    };
+
+    Ok(used_crates(&file)
+        .into_iter()
+        .filter(|name| !declared.contains(name))
+        .collect())
+}
+
+/// `@allow_deps` を指定した場合のモデルへの指示
+pub(crate) fn prompt(allowed: &[String]) -> String {
+    match allowed.is_empty() {
+        true => "※ 標準ライブラリと、この指示の中で使用を指定したクレート以外のクレートは使わないでください。".to_string(),
+        false => format!(
+            "※ 標準ライブラリと、この指示の中で使用を指定したクレート以外には、 {} のみ使用できます。",
+            allowed
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(" 、 ")
+        ),
+    }
+}
+
+/// 外部クレートを理由に採用できなかった返答を書き直させる指示
+pub(crate) fn retry_prompt(reason: &str) -> String {
+    format!(
+        "※ このコードは使えません ({})。そのクレートを使わず、標準ライブラリと使用を許可したクレートのみで書き直してください。",
+        reason
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{declared_crates, used_crates};
+
+    #[test]
+    fn test_used_crates() {
+        let file = syn::parse_file(
+            r#"
+use std::io::{self, Write};
+use itertools::Itertools;
+use dncl_runtime::Value;
+
+mod util {
+    pub fn double(x: i64) -> i64 { x * 2 }
+}
+
+fn main() {
+    let x: u32 = rand::random();
+    let v: Vec<i64> = Vec::new();
+    let _ = i64::MAX;
+    let _ = util::double(1);
+    io::stdout().flush().unwrap();
+    println!("{}", ::regex::escape("a"));
+}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            used_crates(&file),
+            vec!["dncl_runtime", "itertools", "rand", "regex"]
+        );
+    }
+
+    #[test]
+    fn test_used_crates_local_items() {
+        let file = syn::parse_file(
+            r#"
+use Direction::*;
+use Kind::{A, B};
+use self::consts::LIMIT;
+use super::helper;
+use crate::util::double;
+
+mod consts {
+    pub const LIMIT: i64 = 10;
+}
+
+enum Direction { Up, Down }
+enum Kind { A, B }
+
+fn main() {
+    struct Point { x: i64 }
+    let _ = Point { x: 1 }.x;
+    let _ = (Up, A, LIMIT);
+}
+"#,
+        )
+        .unwrap();
+
+        assert!(used_crates(&file).is_empty());
+    }
+
+    #[test]
+    fn test_declared_crates() {
+        let manifest = toml::from_str(
+            r#"
+[package]
+name = "dncl-problems"
+
+[dependencies]
+dncl_trans = "1.0.1"
+dncl-runtime = "1.0.1"
+
+[target.'cfg(unix)'.dev-dependencies]
+rand = "0.8"
+"#,
+        )
+        .unwrap();
+
+        let declared = declared_crates(&manifest);
+        for name in ["dncl_problems", "dncl_trans", "dncl_runtime", "rand"] {
+            assert!(declared.contains(name));
+        }
+        assert!(!declared.contains("itertools"));
+    }
+}
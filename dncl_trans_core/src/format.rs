@@ -0,0 +1,268 @@
+//! DNCLコードの整形
+//!
+//! 試験問題のPDF等からコピーしたコードを、正規化 ( [`crate::normalize`] ) した上で
+//! 制御範囲の記号・演算子の前後の空白・制御文の語句と末尾の `:` を揃えた表記に書き直す
+
+use crate::config::Dialect;
+use crate::normalize::normalize;
+use crate::token::{tokenize, Kind, Token};
+
+/// 整形の設定
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FormatOptions {
+    /// 行頭に `(1)` のような行番号を付ける
+    pub line_numbers: bool,
+    /// DNCLの文法。 `Dialect::R7` の場合は制御文の末尾の `:` を補う
+    pub dialect: Dialect,
+}
+
+/// 表記を揃える制御文の語句。空白の有無の違いだけのものをこの表記にする
+const KEYWORDS: &[&str] = &[
+    "を 定義する",
+    "を返す",
+    "ずつ増やしながら繰り返す",
+    "ずつ減らしながら繰り返す",
+    "の間繰り返す",
+    "を実行する",
+    "を実行し",
+    "そうでなくもし",
+    "そうでなければ",
+];
+
+/// 続いて並ぶ日本語の語が制御文の語句であれば、1つの語にまとめて表記を揃える
+///
+/// 語句の前には (行頭でなければ) 空白を入れる
+fn canonicalize_keywords(tokens: Vec<Token<'_>>) -> Vec<Token<'_>> {
+    let mut canonicalized = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let run = tokens[i..]
+            .iter()
+            .take_while(|token| token.kind == Kind::Word)
+            .count();
+        let joined = tokens[i..i + run]
+            .iter()
+            .map(|token| token.text)
+            .collect::<String>();
+        let keyword = KEYWORDS
+            .iter()
+            .find(|keyword| keyword.replace(' ', "") == joined);
+
+        match keyword {
+            Some(keyword) if run > 0 => {
+                canonicalized.push(Token {
+                    kind: Kind::Word,
+                    text: keyword,
+                    spaced: true,
+                    start: tokens[i].start,
+                });
+                i += run;
+            }
+            _ => {
+                canonicalized.push(tokens[i]);
+                i += 1;
+            }
+        }
+    }
+
+    canonicalized
+}
+
+/// 変数名・数値・リテラルのトークンか
+fn value_kind(kind: Kind) -> bool {
+    matches!(kind, Kind::Ident | Kind::Num | Kind::Str | Kind::Input)
+}
+
+/// 演算子の前後・カンマの後と日本語の語の前後に空白を1つ入れ、括弧の内側の空白を除く
+fn format_body(body: &str) -> String {
+    let mut formatted = String::new();
+    let mut prev: Option<Token> = None;
+    // 直前が単項演算子で、次のトークンとの間に空白を入れない
+    let mut unary = false;
+
+    for token in canonicalize_keywords(tokenize(body)) {
+        let space = match (prev.map(|prev| prev.kind), token.kind) {
+            (None, _) => false,
+            _ if unary => false,
+            (_, Kind::Comment) => true,
+            (Some(Kind::Open(_)), _) | (_, Kind::Close(_) | Kind::Comma | Kind::Colon) => false,
+            (Some(Kind::Comma | Kind::Op), _) => true,
+            (_, Kind::Op) => true,
+            (Some(Kind::Ident | Kind::Word), Kind::Open('[')) => false,
+            // `iを0から9まで` のように詰めて書かれた日本語の語と値の間
+            (Some(Kind::Word), kind) if value_kind(kind) => true,
+            (Some(kind), Kind::Word) if value_kind(kind) || matches!(kind, Kind::Close(_)) => true,
+            _ => token.spaced,
+        };
+        if space {
+            formatted.push(' ');
+        }
+
+        unary = (token.is(Kind::Op, "-") || token.is(Kind::Op, "+"))
+            && !prev.is_some_and(|prev| prev.ends_value());
+        formatted.push_str(token.text);
+        prev = Some(token);
+    }
+
+    formatted
+}
+
+/// R7の制御文の末尾に `:` がなければ補う
+fn complete_colon(body: String) -> String {
+    let (code, comment) = match body.find(" #") {
+        Some(i) if !body[..i].contains(['"', '「']) => body.split_at(i),
+        _ => (body.as_str(), ""),
+    };
+    let needs_colon = (code.starts_with("もし") || code.starts_with("そうでなくもし"))
+        && code.ends_with("ならば")
+        || code == "そうでなければ"
+        || code.ends_with("繰り返す")
+        || code.ends_with("定義する");
+
+    match needs_colon {
+        true => format!("{}:{}", code, comment),
+        false => body,
+    }
+}
+
+/// 前の制御文の続き ( `そうでなければ` 等) の行か
+fn is_continuation(body: &str) -> bool {
+    body.starts_with("そうでなければ") || body.starts_with("そうでなくもし")
+}
+
+/// DNCLコードを整形する
+///
+/// 制御範囲の記号は入れ子の深さ (行頭の記号の数) から付け直す。
+/// その深さの範囲が次の行で終わる場合は `└` 、続く場合は `│` とする
+pub fn format(dncl_source: &str, options: &FormatOptions) -> String {
+    let lines = normalize(dncl_source)
+        .lines()
+        .map(|line| {
+            let body = line.trim_start_matches([' ', '│', '└', '├']);
+            let depth = line[..line.len() - body.len()]
+                .chars()
+                .filter(|c| matches!(c, '│' | '└' | '├'))
+                .count();
+            let body = format_body(body);
+            let body = match options.dialect {
+                Dialect::R7 => complete_colon(body),
+                Dialect::Legacy => body,
+            };
+
+            (depth, body)
+        })
+        .collect::<Vec<_>>();
+
+    let width = format!("({})", lines.len()).len();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, (depth, body))| {
+            let (next_depth, next_body) = lines
+                .get(i + 1)
+                .map_or((0, ""), |(depth, body)| (*depth, body.as_str()));
+
+            let mut line = String::new();
+            if options.line_numbers {
+                line.push_str(&format!("{:<width$} ", format!("({})", i + 1)));
+            }
+            for level in 1..=*depth {
+                let bar = if level == *depth && body.starts_with("を実行し") {
+                    // 旧仕様の `を実行し,そうでなければ`
+                    '├'
+                } else if next_depth < level
+                    && !(next_depth + 1 == level && is_continuation(next_body))
+                {
+                    '└'
+                } else {
+                    '│'
+                };
+                line.push(bar);
+                line.push(' ');
+            }
+            line.push_str(body);
+
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format, format_body, FormatOptions};
+    use crate::config::Dialect;
+
+    #[test]
+    fn test_format_body() {
+        assert_eq!(format_body("x=x+1,y=-x*2"), "x = x + 1, y = -x * 2");
+        assert_eq!(
+            format_body("表示する( \"a=b\" ,Data[ i ,j ] )"),
+            "表示する(\"a=b\", Data[i, j])"
+        );
+        assert_eq!(
+            format_body("もし x>=3 and not y ならば:"),
+            "もし x >= 3 and not y ならば:"
+        );
+        assert_eq!(
+            format_body("nyuryoku={外部からの入力}#入力"),
+            "nyuryoku = {外部からの入力} #入力"
+        );
+        assert_eq!(
+            format_body("kazu = 要素数(Data) - 1"),
+            "kazu = 要素数(Data) - 1"
+        );
+        assert_eq!(
+            format_body("関数 hyouji(a,b)を定義する:"),
+            "関数 hyouji(a, b) を 定義する:"
+        );
+        assert_eq!(
+            format_body("i を 0 から 9 まで 1 ずつ 増やしながら 繰り返す:"),
+            "i を 0 から 9 まで 1 ずつ増やしながら繰り返す:"
+        );
+        assert_eq!(format_body("x<3の間繰り返す:"), "x < 3 の間繰り返す:");
+        assert_eq!(
+            format_body("iを0から9まで1ずつ増やしながら繰り返す:"),
+            "i を 0 から 9 まで 1 ずつ増やしながら繰り返す:"
+        );
+        assert_eq!(format_body("もしx>3ならば:"), "もし x > 3 ならば:");
+        assert_eq!(format_body("x を 返す"), "x を返す");
+    }
+
+    #[test]
+    fn test_format() {
+        let source = "(1) Kosuu＝3\n(2) もし Kosuu＞0 ならば\n(3) │  i を 0 から 9 まで 1 ずつ増やしながら繰り返す：\n(4) │ │ 表示する(i)\n(5) |  x=x-1\n(6) そうでなければ\n(7) |=  x=x*2";
+        let formatted = format(source, &FormatOptions::default());
+
+        assert_eq!(
+            formatted,
+            "Kosuu = 3\nもし Kosuu > 0 ならば:\n│ i を 0 から 9 まで 1 ずつ増やしながら繰り返す:\n│ └ 表示する(i)\n│ x = x - 1\nそうでなければ:\n└ x = x * 2"
+        );
+        // 整形済みのコードは変わらない
+        assert_eq!(format(&formatted, &FormatOptions::default()), formatted);
+
+        let options = FormatOptions {
+            line_numbers: true,
+            ..FormatOptions::default()
+        };
+        let numbered = format(&formatted, &options);
+        assert!(numbered.starts_with("(1) Kosuu = 3\n(2) もし"));
+        assert_eq!(format(&numbered, &options), numbered);
+    }
+
+    #[test]
+    fn test_format_legacy() {
+        let source = "もし x=3 ならば\n│ x←x-1\n│ を実行し,そうでなければ\n│ y←y×2\n│ を実行する";
+        let options = FormatOptions {
+            dialect: Dialect::Legacy,
+            ..FormatOptions::default()
+        };
+
+        assert_eq!(
+            format(source, &options),
+            "もし x = 3 ならば\n│ x ← x - 1\n├ を実行し, そうでなければ\n│ y ← y × 2\n└ を実行する"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, Item, ItemFn, LitStr, Token,
+};
+
+/// `@functions { ... }` で宣言された、問題文で定義される関数
+#[derive(Clone)]
+pub enum Function {
+    /// `差分の絶対値(a, b) = "aとbの差の絶対値を返す"` のような説明による宣言
+    Described {
+        name: Ident,
+        params: Vec<Ident>,
+        description: LitStr,
+    },
+    /// Rustによる実装。生成されたコードと一緒に展開される
+    Implemented(Box<ItemFn>),
+}
+
+impl Function {
+    pub fn name(&self) -> &Ident {
+        match self {
+            Function::Described { name, .. } => name,
+            Function::Implemented(item_fn) => &item_fn.sig.ident,
+        }
+    }
+
+    /// プロンプトに載せる1行
+    fn prompt_line(&self) -> String {
+        match self {
+            Function::Described {
+                name,
+                params,
+                description,
+            } => {
+                let params = params
+                    .iter()
+                    .map(Ident::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!("- `{}({})`: {}", name, params, description.value())
+            }
+            Function::Implemented(item_fn) => format!(
+                "- `{}`: Rustで定義済みです。この関数は定義せず、そのまま呼び出してください。",
+                signature(item_fn)
+            ),
+        }
+    }
+}
+
+/// 本体を除いた `fn` のシグネチャを整形する
+fn signature(item_fn: &ItemFn) -> String {
+    let file = syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items: vec![Item::Fn(ItemFn {
+            attrs: Vec::new(),
+            block: Box::new(syn::parse_quote!({})),
+            ..item_fn.clone()
+        })],
+    };
+
+    prettyplease::unparse(&file)
+        .trim_end()
+        .trim_end_matches("{}")
+        .trim_end()
+        .to_string()
+}
+
+impl Parse for Function {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![fn]) || input.peek(Token![pub]) || input.peek(Token![#]) {
+            return Ok(Function::Implemented(Box::new(input.parse()?)));
+        }
+
+        let name = input.parse::<Ident>()?;
+        let content;
+        parenthesized!(content in input);
+        let params = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?
+            .into_iter()
+            .collect();
+        input.parse::<Token![=]>()?;
+        let description = input.parse::<LitStr>()?;
+
+        Ok(Function::Described {
+            name,
+            params,
+            description,
+        })
+    }
+}
+
+/// `@functions` の中身
+#[derive(Clone, Default)]
+pub struct Functions(pub Vec<Function>);
+
+impl Parse for Functions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        braced!(content in input);
+
+        let mut functions: Vec<Function> = Vec::new();
+        while !content.is_empty() {
+            let function = content.parse::<Function>()?;
+
+            if functions.iter().any(|f| f.name() == function.name()) {
+                return Err(syn::Error::new(
+                    function.name().span(),
+                    format!("function `{}` is declared twice", function.name()),
+                ));
+            }
+            functions.push(function);
+
+            // `;` , `,` のパース
+            if content.peek(Token![,]) {
+                content.parse::<Token![,]>()?;
+            } else if content.peek(Token![;]) {
+                content.parse::<Token![;]>()?;
+            }
+        }
+
+        Ok(Functions(functions))
+    }
+}
+
+impl Functions {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// モデルへ渡す関数の説明
+    pub fn prompt(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let lines = self
+            .0
+            .iter()
+            .map(Function::prompt_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        Some(format!(
+            "このプログラムでは以下の関数が問題文で定義されています。関数名から推測せず、この説明に従ってください。\n\n{}",
+            lines
+        ))
+    }
+
+    /// Rustで実装された関数
+    pub fn implemented(&self) -> Vec<ItemFn> {
+        self.0
+            .iter()
+            .filter_map(|function| match function {
+                Function::Implemented(item_fn) => Some(item_fn.as_ref().clone()),
+                Function::Described { .. } => None,
+            })
+            .collect()
+    }
+}
+
+/// 関数を生成されたコードに加える
+///
+/// モデルが同名の関数を定義してしまった場合は、こちらの実装で置き換える
+pub(crate) fn link(tokens: TokenStream, items: Vec<ItemFn>) -> TokenStream {
+    if items.is_empty() {
+        return tokens;
+    }
+
+    let Ok(mut file) = syn::parse2::<syn::File>(tokens.clone()) else {
+        return tokens;
+    };
+
+    file.items.retain(|item| match item {
+        Item::Fn(item_fn) => !items
+            .iter()
+            .any(|linked| linked.sig.ident == item_fn.sig.ident),
+        _ => true,
+    });
+    file.items.extend(items.into_iter().map(Item::Fn));
+
+    file.into_token_stream()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{link, Functions};
+
+    #[test]
+    fn test_functions() {
+        let functions: Functions = syn::parse_str(
+            r#"{
+    二進で表示する(x) = "x を2進数で表示する";
+    fn 差分の絶対値(a: i64, b: i64) -> i64 {
+        (a - b).abs()
+    }
+}"#,
+        )
+        .unwrap();
+
+        let prompt = functions.prompt().unwrap();
+        assert!(prompt.contains("- `二進で表示する(x)`: x を2進数で表示する"));
+        assert!(prompt.contains("- `fn 差分の絶対値(a: i64, b: i64) -> i64`: Rustで定義済み"));
+
+        // モデルが定義した同名の関数は置き換えられる
+        let generated = quote::quote! {
+            fn 差分の絶対値(a: i64, b: i64) -> i64 { a - b }
+            fn main() { println!("{}", 差分の絶対値(1, 3)); }
+        };
+        let file: syn::File = syn::parse2(link(generated, functions.implemented())).unwrap();
+
+        assert_eq!(file.items.len(), 2);
+        assert!(quote::quote!(#file).to_string().contains("abs"));
+
+        let duplicated: Result<Functions, _> = syn::parse_str(r#"{ f(x) = "a", f(y) = "b" }"#);
+        assert!(duplicated.is_err());
+    }
+}
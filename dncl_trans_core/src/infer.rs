@@ -0,0 +1,507 @@
+//! DNCLの変数の型と再代入の有無の推論
+//!
+//! `@semantics = "inferred"` で、推論結果をモデルへの指示と生成コードの確認に使う。
+//! 推論できなかった変数は `dncl_runtime::Value` で扱わせる
+
+use std::fmt;
+
+use crate::source::{lines, Line};
+use crate::token::{tokenize, Kind, Token};
+
+/// 推論された型
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Ty {
+    /// まだ何も分かっていない (空の配列の要素など)
+    Any,
+    Int,
+    Float,
+    Str,
+    Bool,
+    Array(Box<Ty>),
+    /// 型が定まらない
+    Unknown,
+}
+
+impl Ty {
+    /// 2つの型をどちらも表せる型にまとめる
+    fn unify(self, other: Ty) -> Ty {
+        match (self, other) {
+            (Ty::Any, ty) | (ty, Ty::Any) => ty,
+            (Ty::Int, Ty::Float) | (Ty::Float, Ty::Int) => Ty::Float,
+            (Ty::Array(a), Ty::Array(b)) => Ty::Array(Box::new(a.unify(*b))),
+            (a, b) if a == b => a,
+            _ => Ty::Unknown,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Ty::Int | Ty::Float)
+    }
+
+    /// 型が定まらない部分を含むか
+    pub fn is_unknown(&self) -> bool {
+        match self {
+            Ty::Unknown => true,
+            Ty::Array(ty) => ty.is_unknown(),
+            _ => false,
+        }
+    }
+
+    /// 添字で `dims` 回アクセスした要素の型
+    fn element(&self, dims: usize) -> Ty {
+        match (self, dims) {
+            (ty, 0) => ty.clone(),
+            (Ty::Array(ty), dims) => ty.element(dims - 1),
+            (Ty::Any, _) => Ty::Any,
+            _ => Ty::Unknown,
+        }
+    }
+}
+
+/// Rustでの型名
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Int => write!(f, "i64"),
+            Ty::Float => write!(f, "f64"),
+            Ty::Str => write!(f, "String"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Array(ty) => write!(f, "Vec<{}>", ty),
+            Ty::Any | Ty::Unknown => write!(f, "dncl_runtime::Value"),
+        }
+    }
+}
+
+/// 変数ごとの推論結果
+#[derive(Debug, PartialEq)]
+pub(crate) struct Var {
+    pub name: String,
+    pub ty: Ty,
+    /// 代入された回数
+    pub assignments: usize,
+    /// 要素の変更 (添字への代入や「すべての値を〜にする」) があるか
+    pub element_mutated: bool,
+    /// 「〜を … ずつ増やしながら繰り返す」の変数か
+    pub loop_var: bool,
+}
+
+impl Var {
+    /// `let mut` で宣言する必要があるか
+    pub fn mutated(&self) -> bool {
+        self.assignments > 1 || self.element_mutated
+    }
+}
+
+/// 式の型を求める
+struct ExprTyper<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    vars: &'a [Var],
+}
+
+impl<'a> ExprTyper<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn var_ty(&self, name: &str) -> Ty {
+        self.vars
+            .iter()
+            .find(|var| var.name == name)
+            .map_or(Ty::Unknown, |var| var.ty.clone())
+    }
+
+    fn expr(&mut self) -> Ty {
+        let mut ty = self.unary();
+        let mut boolean = false;
+
+        while let Some(Token {
+            kind: Kind::Op,
+            text: op,
+            ..
+        }) = self.peek()
+        {
+            self.next();
+            let rhs = self.unary();
+
+            ty = match op {
+                "==" | "=" | "!=" | ">=" | "<=" | "≠" | "≧" | "≦" | "<" | ">" | "and" | "or" =>
+                {
+                    boolean = true;
+                    ty
+                }
+                "+" | "＋" => match (ty, rhs) {
+                    (Ty::Str, _) | (_, Ty::Str) => Ty::Str,
+                    (a, b) => a.unify(b),
+                },
+                "/" => match ty.is_numeric() && rhs.is_numeric() {
+                    true => Ty::Float,
+                    false => Ty::Unknown,
+                },
+                "÷" | "div" => match ty.is_numeric() && rhs.is_numeric() {
+                    true => Ty::Int,
+                    false => Ty::Unknown,
+                },
+                _ => match ty.is_numeric() && rhs.is_numeric() {
+                    true => ty.unify(rhs),
+                    false => Ty::Unknown,
+                },
+            };
+        }
+
+        match boolean {
+            true => Ty::Bool,
+            false => ty,
+        }
+    }
+
+    fn unary(&mut self) -> Ty {
+        match self.peek().filter(|token| token.kind == Kind::Op) {
+            Some(Token {
+                text: "-" | "－", ..
+            }) => {
+                self.next();
+                self.unary()
+            }
+            Some(Token { text: "not", .. }) => {
+                self.next();
+                self.unary();
+                Ty::Bool
+            }
+            _ => self.atom(),
+        }
+    }
+
+    /// `(` ・ `[` の後から対応する閉じ括弧までの式を読む
+    fn list(&mut self) -> Vec<Ty> {
+        let mut tys = Vec::new();
+
+        loop {
+            match self.peek().map(|token| token.kind) {
+                None => break,
+                Some(Kind::Close(_)) => {
+                    self.next();
+                    break;
+                }
+                Some(Kind::Comma) => {
+                    self.next();
+                }
+                Some(_) => {
+                    let pos = self.pos;
+                    tys.push(self.expr());
+                    // 読み進められなかった場合は読み飛ばす
+                    if self.pos == pos {
+                        self.next();
+                    }
+                }
+            }
+        }
+
+        tys
+    }
+
+    fn atom(&mut self) -> Ty {
+        let Some(token) = self.next() else {
+            return Ty::Unknown;
+        };
+
+        match token.kind {
+            Kind::Num if token.text.contains('.') => Ty::Float,
+            Kind::Num => Ty::Int,
+            Kind::Str => Ty::Str,
+            // 読み込む型は代入先の変数の他の使われ方から決める
+            Kind::Input => Ty::Any,
+            Kind::Open('[') => {
+                Ty::Array(Box::new(self.list().into_iter().fold(Ty::Any, Ty::unify)))
+            }
+            Kind::Open(_) => self.list().into_iter().next().unwrap_or(Ty::Unknown),
+            Kind::Ident | Kind::Word => match self.peek().map(|token| token.kind) {
+                Some(Kind::Open('(')) => {
+                    self.next();
+                    self.list();
+                    match token.text {
+                        "要素数" | "整数" => Ty::Int,
+                        "乱数" => Ty::Float,
+                        _ => Ty::Unknown,
+                    }
+                }
+                Some(Kind::Open('[')) => {
+                    self.next();
+                    let dims = self.list().len();
+                    self.var_ty(token.text).element(dims)
+                }
+                _ => self.var_ty(token.text),
+            },
+            _ => Ty::Unknown,
+        }
+    }
+}
+
+fn expr_ty(expr: &str, vars: &[Var]) -> Ty {
+    // 式に関係しない記号は読み飛ばす
+    let tokens = tokenize(expr)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, Kind::Colon | Kind::Comment | Kind::Other))
+        .collect();
+    let mut typer = ExprTyper {
+        tokens,
+        pos: 0,
+        vars,
+    };
+
+    typer.expr()
+}
+
+/// 比較演算子ではない `=` の位置で左辺と右辺に分ける
+pub(crate) fn split_assignment(statement: &str) -> Option<(&str, &str)> {
+    let bytes = statement.as_bytes();
+
+    statement.match_indices('=').find_map(|(i, _)| {
+        let prev = i.checked_sub(1).map(|j| bytes[j]);
+        let next = bytes.get(i + 1);
+        match (prev, next) {
+            (Some(b'=' | b'!' | b'<' | b'>' | b'|'), _) | (_, Some(b'=')) => None,
+            _ => Some((statement[..i].trim(), statement[i + 1..].trim())),
+        }
+    })
+}
+
+/// `kosu = 3, kingaku = 300` のように1行に並んだ代入文を分ける
+pub(crate) fn split_statements(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_str = !in_str,
+            '(' | '[' | '（' if !in_str => depth += 1,
+            ')' | ']' | '）' if !in_str => depth -= 1,
+            ',' if !in_str && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    // すべてが代入文の場合のみ分ける (関数呼び出しの引数等と区別する)
+    match parts.len() > 1 && parts.iter().all(|part| split_assignment(part).is_some()) {
+        true => parts,
+        false => vec![text],
+    }
+}
+
+fn is_ident(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+struct Inferrer {
+    vars: Vec<Var>,
+}
+
+impl Inferrer {
+    fn var(&mut self, name: &str) -> &mut Var {
+        let i = match self.vars.iter().position(|var| var.name == name) {
+            Some(i) => i,
+            None => {
+                self.vars.push(Var {
+                    name: name.to_string(),
+                    ty: Ty::Any,
+                    assignments: 0,
+                    element_mutated: false,
+                    loop_var: false,
+                });
+                self.vars.len() - 1
+            }
+        };
+
+        &mut self.vars[i]
+    }
+
+    fn assign(&mut self, name: &str, ty: Ty) {
+        let var = self.var(name);
+        var.ty = std::mem::replace(&mut var.ty, Ty::Any).unify(ty);
+        var.assignments += 1;
+    }
+
+    /// `Data[i, j] = x` のような要素への代入
+    fn assign_element(&mut self, name: &str, dims: usize, ty: Ty) {
+        let ty = (0..dims).fold(ty, |ty, _| Ty::Array(Box::new(ty)));
+        let var = self.var(name);
+        var.ty = std::mem::replace(&mut var.ty, Ty::Any).unify(ty);
+        var.element_mutated = true;
+    }
+
+    fn statement(&mut self, text: &str) {
+        // 制御文
+        if text.ends_with("ならば:")
+            || text.ends_with("ならば：")
+            || text.starts_with("そうでなければ")
+            || text.ends_with("の間繰り返す:")
+            || text.ends_with("の間繰り返す：")
+            || text.contains("定義する")
+            || text.ends_with("を返す")
+        {
+            return;
+        }
+
+        // `x を 0 から 9 まで 1 ずつ増やしながら繰り返す:`
+        if text.contains("ずつ増やしながら") || text.contains("ずつ減らしながら") {
+            if let Some((name, rest)) = text.split_once('を') {
+                let name = name.trim();
+                let ty = rest
+                    .split(['か', 'ま'])
+                    .next()
+                    .map_or(Ty::Int, |start| expr_ty(start, &self.vars));
+                let var = self.var(name);
+                var.ty = std::mem::replace(&mut var.ty, Ty::Any).unify(ty);
+                var.loop_var = true;
+            }
+            return;
+        }
+
+        // `Tokuten のすべての値を 0 にする`
+        if let Some((name, rest)) = text.split_once("のすべての値を") {
+            let value = rest.trim_end_matches("にする").trim();
+            let ty = expr_ty(value, &self.vars);
+            let name = name.trim();
+            let dims = match &self.var(name).ty {
+                Ty::Array(inner) if matches!(**inner, Ty::Array(_)) => 2,
+                _ => 1,
+            };
+            self.assign_element(name, dims, ty);
+            return;
+        }
+
+        for statement in split_statements(text) {
+            let Some((lhs, rhs)) = split_assignment(statement) else {
+                continue;
+            };
+            let ty = expr_ty(rhs, &self.vars);
+
+            match lhs.split_once('[') {
+                Some((name, index)) if is_ident(name.trim()) => {
+                    let dims = index.matches(',').count() + 1;
+                    self.assign_element(name.trim(), dims, ty);
+                }
+                None if is_ident(lhs) => self.assign(lhs, ty),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// DNCLソースコード中の変数の型と再代入の有無を推論する
+pub(crate) fn infer(dncl_source: &str) -> Vec<Var> {
+    let mut inferrer = Inferrer { vars: Vec::new() };
+
+    for Line { text, .. } in lines(dncl_source) {
+        inferrer.statement(&text);
+    }
+
+    inferrer
+        .vars
+        .into_iter()
+        .map(|var| Var {
+            ty: match var.ty {
+                Ty::Any => Ty::Unknown,
+                ty => ty,
+            },
+            ..var
+        })
+        .collect()
+}
+
+/// 推論結果をモデルへの指示にする
+pub(crate) fn prompt(vars: &[Var]) -> String {
+    let lines = vars
+        .iter()
+        .map(|var| {
+            let note = match (var.loop_var, var.ty.is_unknown(), var.mutated()) {
+                (true, _, _) => "繰り返しの変数",
+                (_, true, _) => "型が定まらないため動的型",
+                (_, _, true) if var.element_mutated && var.assignments <= 1 => {
+                    "要素の変更あり: `let mut`"
+                }
+                (_, _, true) => "再代入あり: `let mut`",
+                (_, _, false) => "再代入なし: `let`",
+            };
+            format!("- `{}`: `{}` ({})", var.name, var.ty, note)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "※ このプログラムの変数の型と再代入の有無を解析した結果です。変数名は小文字にして構いませんが、型と `let` ・ `let mut` はこの通りにしてください。\n\n{}",
+        lines
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{infer, Ty};
+
+    #[test]
+    fn test_infer() {
+        let source = r#"
+Akibi = [5, 3, 4]
+buinsu = 3
+tantou = 1
+heikin = buinsu / 2
+namae = "部員" + tantou
+kosu = 3, kingaku = 300
+buin を 2 から buinsu まで 1 ずつ増やしながら繰り返す:
+│  もし Akibi[buin] < Akibi[tantou] ならば:
+└  └ tantou = buin
+Data[2, 1] = 0.5
+Tokuten のすべての値を 0 にする
+x = {外部からの入力}
+kosuu = {外部からの入力}
+kosuu = kosuu + 1
+kazu = 要素数（Akibi） － 1
+表示する("次の工芸品の担当は部員", tantou, "です。")
+"#;
+        let vars = infer(source);
+        let find = |name: &str| vars.iter().find(|var| var.name == name).unwrap();
+
+        assert_eq!(find("Akibi").ty, Ty::Array(Box::new(Ty::Int)));
+        assert!(!find("Akibi").mutated());
+        assert_eq!(find("buinsu").ty, Ty::Int);
+        assert!(!find("buinsu").mutated());
+        assert_eq!(find("tantou").ty, Ty::Int);
+        assert!(find("tantou").mutated());
+        assert_eq!(find("heikin").ty, Ty::Float);
+        assert_eq!(find("namae").ty, Ty::Str);
+        assert_eq!(find("kingaku").ty, Ty::Int);
+        assert!(find("buin").loop_var);
+        assert_eq!(
+            find("Data").ty,
+            Ty::Array(Box::new(Ty::Array(Box::new(Ty::Float))))
+        );
+        assert!(find("Data").mutated());
+        assert!(find("Tokuten").element_mutated);
+        assert!(find("x").ty.is_unknown());
+        assert_eq!(find("kazu").ty, Ty::Int);
+        assert_eq!(find("x").ty.to_string(), "dncl_runtime::Value");
+        // 入力を代入した変数も、他の代入から型が決まる
+        assert_eq!(find("kosuu").ty, Ty::Int);
+    }
+
+    #[test]
+    fn test_unify() {
+        assert_eq!(Ty::Int.unify(Ty::Float), Ty::Float);
+        assert_eq!(Ty::Any.unify(Ty::Str), Ty::Str);
+        assert_eq!(Ty::Int.unify(Ty::Str), Ty::Unknown);
+    }
+}
@@ -0,0 +1,97 @@
+//! `{外部からの入力}` の扱い
+//!
+//! `{外部からの入力}` は生成コードと一緒に展開する `dncl_input::<T>()` で1行ずつ読み込ませる。
+//! 読み込み元は環境変数 `DNCL_INPUTS` (改行区切り) 、 `@inputs_script` 、標準入力の順に決まる
+
+use syn::ItemFn;
+
+use crate::source::{lines, Line};
+use crate::token::{tokenize, Kind};
+
+/// 入力を読み込む関数名
+pub(crate) const INPUT_FN: &str = "dncl_input";
+
+/// 実行時に入力を与える環境変数名
+pub const INPUTS_ENV: &str = "DNCL_INPUTS";
+
+/// モデルへの指示
+pub(crate) const INPUT_PROMPT: &str = "※ `{外部からの入力}` は、定義済みの関数 `dncl_input::<T>()` を呼び出して1回につき1行ずつ読み込んでください。 `T` は代入先の変数の型 ( `i64` 、 `f64` 、 `String` など `FromStr` を実装する型) です。この関数は定義せず、標準入力を直接読み込まないでください。";
+
+/// `{外部からの入力}` を使っているか (コメントや文字列リテラルの中は除く)
+pub(crate) fn uses_input(dncl_source: &str) -> bool {
+    lines(dncl_source).iter().any(|Line { text, .. }| {
+        tokenize(text)
+            .iter()
+            .any(|token| token.kind == Kind::Input && token.text.contains("外部からの入力"))
+    })
+}
+
+/// 生成コードと一緒に展開する `dncl_input` 関数
+pub(crate) fn input_fn(script: Option<&[String]>) -> ItemFn {
+    let script = match script {
+        Some(script) => quote::quote! { Some(vec![#(#script.to_string()),*]) },
+        None => quote::quote! { None },
+    };
+
+    syn::parse_quote! {
+        #[allow(dead_code)]
+        fn dncl_input<T: ::std::str::FromStr>() -> T
+        where
+            T::Err: ::std::fmt::Debug,
+        {
+            static SCRIPTED: ::std::sync::OnceLock<
+                Option<::std::sync::Mutex<::std::collections::VecDeque<String>>>,
+            > = ::std::sync::OnceLock::new();
+
+            let scripted = SCRIPTED.get_or_init(|| {
+                let inputs: Option<Vec<String>> = match ::std::env::var(#INPUTS_ENV) {
+                    Ok(inputs) => Some(inputs.lines().map(String::from).collect()),
+                    Err(_) => #script,
+                };
+                inputs.map(|inputs| ::std::sync::Mutex::new(inputs.into()))
+            });
+
+            let line = match scripted {
+                Some(inputs) => inputs
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .expect("外部からの入力が足りません"),
+                None => {
+                    let mut line = String::new();
+                    ::std::io::stdin()
+                        .read_line(&mut line)
+                        .expect("標準入力を読み込めません");
+                    line
+                }
+            };
+            let line = line.trim();
+
+            line.parse()
+                .unwrap_or_else(|e| panic!("外部からの入力 {:?} を読み込めません: {:?}", line, e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{input_fn, uses_input};
+
+    #[test]
+    fn test_input_fn() {
+        assert!(uses_input("x = {外部からの入力}"));
+        assert!(!uses_input("x = 1"));
+        assert!(uses_input("│ x = ｛外部からの入力｝ # 入力"));
+        // コメントや文字列リテラルの中は対象外
+        assert!(!uses_input("表示する(\"外部からの入力を待つ\")"));
+        assert!(!uses_input("x = 1 # {外部からの入力} は使わない"));
+
+        let script = vec!["42".to_string(), "7".to_string()];
+        let item_fn = input_fn(Some(&script));
+        let code = quote::quote!(#item_fn).to_string();
+
+        assert_eq!(item_fn.sig.ident, "dncl_input");
+        assert!(code.contains("\"42\""));
+        assert!(code.contains("DNCL_INPUTS"));
+    }
+}
@@ -0,0 +1,342 @@
+//! 2021年度以前の大学入試センター試験のDNCL ( `@dialect = "legacy"` )
+//!
+//! トランスパイルはモデルに旧仕様を渡して行う。型推論や返答の確認はR7の文法を前提としているため、
+//! そのための解析用に旧仕様のコードをR7の文法へ書き換える
+
+use std::borrow::Cow;
+
+use crate::config::Dialect;
+use crate::prompt::DNCL_SPEC;
+use crate::source::split_outside_strings;
+use crate::token::{tokenize, Kind};
+
+/// 旧仕様のDNCLの文法
+const LEGACY_DNCL_SYNTAX: &str = r#"大学入試センター試験で使用されていたプログラミング言語DNCL (旧仕様) の仕様を以下に示します。
+
+---
+# 1. 変数・配列
+
+通常の変数例: `kosu`, `kingaku_kei` (変数名は英字で始まる英数字と `_` の並び)
+
+配列変数の例: `Tokuten[3]`, `Data[2, 4]` (配列名は先頭文字が大文字)
+
+※ 配列の添字がいくつから始まるかは問題文の説明に従う
+
+# 2. 文字列
+
+文字列は `「」` (またはダブルクォーテーション `"` ) で囲む
+
+# 3. 代入文
+
+```dncl
+kosu ← 3, kingaku ← 300
+kingaku_goukei ← kingaku × kosu
+Tokuten のすべての値を 0 にする
+```
+
+※ `←` で代入する。 `,` で区切って複数の文を1行で表記できる
+
+# 4. 増減
+
+```dncl
+kosu を 1 増やす
+kingaku を 100 減らす
+```
+
+※ `x を n 増やす` は `x ← x + n` 、 `x を n 減らす` は `x ← x - n` と同じ
+
+# 5. 表示
+
+```dncl
+kosu を表示する
+「合計は」と goukei と「円です」を表示する
+```
+
+※ `と` で区切って文字列や数値を連結して表示する
+
+# 6. 算術演算
+
+加減乗除の四則演算は、 `+` 、 `-` 、 `×` 、 `/` で表す
+整数の除算では、商(整数)を `÷` で、余りを `%` で表す
+
+# 7. 比較演算
+
+`=` (等しい)、 `≠` (等しくない)、 `>` 、 `<` 、 `≧` 、 `≦`
+
+※ 比較の `=` は代入ではない
+
+# 8. 論理演算
+
+`かつ` (論理積)、 `または` (論理和)、 `でない` (否定)
+
+# 9. 制御文 (条件分岐)
+
+```dncl
+もし x < 3 ならば
+│ x ← x + 1
+│ y ← y + 1
+└ を実行する
+```
+
+```dncl
+もし x = 3 ならば
+│ x ← x - 1
+├ を実行し,そうでなければ
+│ y ← y × 2
+└ を実行する
+```
+
+```dncl
+もし x < 3 ならば x ← x + 1 を実行する
+もし x = 3 ならば x ← x - 1 を実行し,そうでなければ y ← y × 2 を実行する
+```
+
+※ `│` と `└` で制御範囲を表し、 `を実行する` の行が制御文の終わりを示す
+
+# 10. 制御文 (繰り返し)
+
+```dncl
+x < 10 の間,
+│ goukei ← goukei + x
+│ x を 1 増やす
+└ を繰り返す
+```
+
+```dncl
+繰り返し,
+│ goukei ← goukei + x
+│ x を 1 増やす
+└ を,x ≧ 10 になるまで実行する
+```
+
+```dncl
+i を 1 から 10 まで 1 ずつ増やしながら,
+│ goukei ← goukei + Data[i]
+└ を繰り返す
+```
+
+※ `〜の間,` は条件が成り立つ間繰り返す (前判定)。 `繰り返し,` は本体を実行してから条件を判定し、条件が成り立つまで繰り返す (後判定)
+※ `減らしながら` もある
+
+# 11. 関数
+
+```dncl
+kazu ← 要素数(Data)
+saikoro ← 整数(乱数() × 6) + 1
+```
+
+※ 関数は基本的に問題中に説明あり (ない場合は関数名より忖度してください)
+"#;
+
+/// `@dialect = "legacy"` の場合にモデルへ渡す仕様
+///
+/// 文法の部分だけを旧仕様に差し替え、トランスパイルについての注意事項は共通にする
+pub(crate) fn spec() -> String {
+    let (_, instructions) = DNCL_SPEC.rsplit_once("\n---\n").unwrap();
+
+    format!("{}\n---\n{}", LEGACY_DNCL_SYNTAX, instructions)
+}
+
+/// 解析に使うR7の文法のDNCLコード
+pub(crate) fn analyzed_source(dncl_source: &str, dialect: Dialect) -> Cow<'_, str> {
+    match dialect {
+        Dialect::R7 => Cow::Borrowed(dncl_source),
+        Dialect::Legacy => Cow::Owned(to_r7(dncl_source)),
+    }
+}
+
+/// 条件式の比較・論理演算をR7の表記にする
+fn condition(cond: &str) -> String {
+    let pieces = split_outside_strings(cond, "=");
+    let mut converted = pieces[0].to_string();
+
+    for piece in &pieces[1..] {
+        // `<=` ・ `>=` ・ `!=` ・ `==` の `=` は比較の `=` ではない
+        if converted.ends_with(['<', '>', '!', '=']) || piece.starts_with('=') {
+            converted.push('=');
+        } else {
+            converted.push_str("==");
+        }
+        converted.push_str(piece);
+    }
+
+    converted
+        .replace('≠', "!=")
+        .replace('≧', ">=")
+        .replace('≦', "<=")
+        .replace("かつ", "and")
+        .replace("または", "or")
+}
+
+/// `x を 1` を目的語 `x` と残りに分ける
+///
+/// 文字列リテラル中の `を` では分けない。空白がなく `を1` ・ `を要素数(Data)` のように続く場合も扱う
+fn split_object(text: &str) -> Option<(&str, &str)> {
+    let wo = tokenize(text)
+        .into_iter()
+        .find(|token| token.kind == Kind::Word && token.text.starts_with('を'))?;
+    let name = text[..wo.start].trim();
+    let rest = text[wo.start + 'を'.len_utf8()..].trim();
+
+    (!name.is_empty() && !rest.is_empty()).then_some((name, rest))
+}
+
+/// 制御文以外の1文をR7の表記にする
+fn statement(text: &str) -> String {
+    let text = text.trim();
+
+    // `x を 1 増やす` / `xを1減らす`
+    for (suffix, op) in [("増やす", '+'), ("減らす", '-')] {
+        if let Some((name, amount)) = text.strip_suffix(suffix).and_then(split_object) {
+            return format!("{} = {} {} {}", name, name, op, amount);
+        }
+    }
+
+    // `「合計は」と goukei を表示する`
+    if let Some(args) = text.strip_suffix("を表示する") {
+        let args = split_outside_strings(args, "と")
+            .iter()
+            .map(|arg| arg.trim())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return format!("表示する({})", args);
+    }
+
+    split_outside_strings(text, ",")
+        .iter()
+        .map(|statement| statement.replace('←', "="))
+        .map(|statement| statement.trim().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 字下げと制御範囲の記号を本体から分ける
+fn split_prefix(line: &str) -> (&str, &str) {
+    let body = line.trim_start_matches([' ', '│', '└', '├']);
+    (&line[..line.len() - body.len()], body)
+}
+
+/// `を実行し,そうでなければ` の行の記号から、本体の制御範囲の分を除く
+fn outer_prefix(prefix: &str) -> String {
+    let mut outer = prefix.trim_end().to_string();
+    outer.pop();
+
+    match outer.trim_end().is_empty() {
+        true => outer.trim_end().to_string(),
+        false => format!("{} ", outer.trim_end()),
+    }
+}
+
+/// 旧仕様のDNCLコード (正規化済み) をR7の文法に書き換える
+///
+/// 制御文の終わりを示す `を実行する` ・ `を繰り返す` の行は取り除く。
+/// 後判定の繰り返し ( `繰り返し,` 〜 `を,… になるまで実行する` ) はR7にないため、本体のみを残す
+pub(crate) fn to_r7(dncl_source: &str) -> String {
+    let mut lines = Vec::new();
+
+    for line in dncl_source.lines() {
+        let (prefix, body) = split_prefix(line);
+        // コメントは解析に使わない
+        let body = split_outside_strings(body, "#")[0].trim();
+        let nested = format!("{}└ ", prefix);
+        let body = body.trim_end_matches([',', '、']);
+
+        if body.is_empty()
+            || body == "繰り返し"
+            || matches!(body, "を実行する" | "を繰り返す")
+            || (body.starts_with("を") && body.ends_with("になるまで実行する"))
+        {
+            continue;
+        }
+
+        // `を実行し,そうでなければ`
+        if body.starts_with("を実行し") && body.ends_with("そうでなければ") {
+            lines.push(format!("{}そうでなければ:", outer_prefix(prefix)));
+            continue;
+        }
+
+        // `もし … ならば` (1行で書く場合は本体が続く)
+        if let Some(rest) = body.strip_prefix("もし") {
+            let Some((cond, rest)) = rest.split_once("ならば") else {
+                lines.push(format!("{}{}", prefix, statement(body)));
+                continue;
+            };
+            lines.push(format!("{}もし{}ならば:", prefix, condition(cond)));
+
+            let rest = rest.trim();
+            if !rest.is_empty() {
+                let rest = rest.trim_end_matches("を実行する").trim();
+                match rest.split_once("を実行し") {
+                    Some((then, otherwise)) => {
+                        let otherwise = otherwise
+                            .trim_start_matches([',', '、', ' '])
+                            .trim_start_matches("そうでなければ")
+                            .trim();
+                        lines.push(format!("{}{}", nested, statement(then)));
+                        lines.push(format!("{}そうでなければ:", prefix));
+                        lines.push(format!("{}{}", nested, statement(otherwise)));
+                    }
+                    None => lines.push(format!("{}{}", nested, statement(rest))),
+                }
+            }
+            continue;
+        }
+
+        // `x < 10 の間,`
+        if let Some(cond) = body.strip_suffix("の間") {
+            lines.push(format!(
+                "{}{} の間繰り返す:",
+                prefix,
+                condition(cond).trim()
+            ));
+            continue;
+        }
+
+        // `i を 1 から 10 まで 1 ずつ増やしながら,`
+        if body.ends_with("ずつ増やしながら") || body.ends_with("ずつ減らしながら")
+        {
+            lines.push(format!("{}{}繰り返す:", prefix, body));
+            continue;
+        }
+
+        lines.push(format!("{}{}", prefix, statement(body)));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{spec, to_r7};
+    use crate::prompt::DNCL_SPEC;
+
+    #[test]
+    fn test_to_r7() {
+        let source = "goukei ← 0, i ← 1\ni ≦ 10 の間,\n│ もし Data[i] = 0 ならば\n│ │ 「0です」と i を表示する\n│ ├ を実行し,そうでなければ\n│ │ goukei ← goukei + Data[i]\n│ └ を実行する\n│ i を 1 増やす\n└ を繰り返す\nもし goukei ≠ 0 かつ goukei > 10 ならば goukei を表示する を実行する";
+
+        assert_eq!(
+            to_r7(source),
+            "goukei = 0, i = 1\ni <= 10 の間繰り返す:\n│ もし Data[i] == 0 ならば:\n│ │ 表示する(「0です」, i)\n│ そうでなければ:\n│ │ goukei = goukei + Data[i]\n│ i = i + 1\nもし goukei != 0 and goukei > 10 ならば:\n└ 表示する(goukei)"
+        );
+
+        assert_eq!(
+            to_r7("k を 1 から 5 まで 1 ずつ増やしながら,\n│ x ← x + k\n└ を繰り返す"),
+            "k を 1 から 5 まで 1 ずつ増やしながら繰り返す:\n│ x = x + k"
+        );
+
+        // PDFからコピーした空白のない表記
+        assert_eq!(
+            to_r7("xを1増やす\nData[i]を要素数(Data)減らす\n「を」と x を表示する"),
+            "x = x + 1\nData[i] = Data[i] - 要素数(Data)\n表示する(「を」, x)"
+        );
+    }
+
+    #[test]
+    fn test_spec() {
+        let spec = spec();
+
+        assert!(spec.contains("←"));
+        // トランスパイルについての注意事項はR7の仕様と共通
+        assert!(spec.ends_with(DNCL_SPEC.rsplit_once("\n---\n").unwrap().1));
+    }
+}
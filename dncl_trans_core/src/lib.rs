@@ -0,0 +1,559 @@
+//! [`dncl_trans`](https://docs.rs/dncl_trans) マクロの実装本体
+//!
+//! proc-macroクレートからは関数を公開できないため、 `cargo-dncl` 等のツールと共有する処理はこちらに置く
+
+use std::path::PathBuf;
+
+use cache::{hash_content, CachedContent};
+use macro_::IntoSynRes;
+use proc_macro2::TokenStream;
+use prompt::Prompt;
+use query::QuerySetting;
+use syn::spanned::Spanned;
+
+mod available_models;
+pub mod cache;
+mod candidate;
+pub mod config;
+mod datetime;
+mod deps;
+pub mod format;
+pub mod functions;
+mod infer;
+pub mod inputs;
+mod legacy;
+pub mod lint;
+mod macro_;
+mod markdown;
+#[cfg(test)]
+mod mock;
+mod normalize;
+mod prompt;
+mod query;
+pub mod random;
+pub mod sampling;
+pub mod security;
+mod source;
+mod token;
+
+use available_models::{check_available, resolve_model};
+use config::Config;
+pub use macro_::MacroInput;
+
+pub fn dncl_impl(input: MacroInput) -> syn::Result<TokenStream> {
+    // DNCLの約束事のチェックはAPIを叩かずにできるため、編集中も警告する
+    let span = input.dncl_code.span();
+    let config = Config::load_manifest().into_syn(span)?;
+    let warnings = lint::warning_tokens(&input.lint_warnings(&config), span);
+
+    if input.editing {
+        let mut tokens = file_content2token_stream(&transpile(input)?.response);
+        tokens.extend(warnings);
+        return Ok(tokens);
+    }
+
+    // `@functions` でRustの実装が与えられた関数と、入力を読み込む関数・乱数を返す関数を一緒に展開する
+    let mut items = input.functions.implemented();
+    let dncl_source = input.dncl_source();
+    if inputs::uses_input(&dncl_source) {
+        items.push(inputs::input_fn(input.inputs_script.as_deref()));
+    }
+    if random::uses_random(&dncl_source) {
+        items.push(random::random_fn(input.rng_seed));
+    }
+
+    let requirements = requirements(&input, &config);
+    let Transpiled {
+        response: res,
+        check_error,
+    } = transpile(input)?;
+    let code = extract_rust_code(&res);
+
+    // キャッシュや `@pin` の返答も含め、確認できないコードや許可されていない操作を含むコードは展開しない
+    check_expanded(&code, &requirements).map_err(|e| syn::Error::new(span, e))?;
+
+    // 生成コードが `Cargo.toml` にないクレートを使っていれば、コンパイルエラーより先に知らせる
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").into_syn(span)?);
+    let missing = deps::missing_crates(&code, &manifest_dir).into_syn(span)?;
+    if !missing.is_empty() {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "generated code uses crates that are not in Cargo.toml: {}. Add them to [dependencies] (e.g. `cargo add {}`), or specify `@allow_deps = []` to request code without them",
+                missing.join(", "),
+                missing.join(" ")
+            ),
+        ));
+    }
+
+    let mut tokens = functions::link(file_content2token_stream(&res), items);
+    tokens.extend(warnings);
+    // どの候補もチェックを通らず採用した返答 (キャッシュを含む) は、その理由を警告する
+    if let Some(check_error) = check_error {
+        tokens.extend(lint::note_tokens(
+            "DnclCheck",
+            &format!(
+                "generated code did not pass the checks: {}. Consider `@regenerate = true` or `@best_of`",
+                check_error
+            ),
+            span,
+        ));
+    }
+
+    Ok(tokens)
+}
+
+/// 展開するコードを確認する
+///
+/// Rustとしてパースできないコードは安全性を確認できないため拒否する。
+/// `@allow_deps` はどの候補も満たさなかった場合でも必ず守らせる
+fn check_expanded(code: &str, requirements: &candidate::Requirements) -> Result<(), String> {
+    let file = syn::parse_file(code)
+        .map_err(|e| format!("generated code cannot be parsed as Rust code: {}", e))?;
+
+    if let Some(violation) = security::scan(&file, &requirements.allowed)
+        .into_iter()
+        .next()
+    {
+        return Err(violation.to_string());
+    }
+
+    candidate::check_allowed_deps(&file, requirements)
+        .map_err(|e| format!("generated code is rejected: {}", e))
+}
+
+/// 返答からRustコードブロックを抜き出す。コードブロックがない場合は返答全体をそのまま返す
+pub fn extract_rust_code(res_code: &str) -> String {
+    let codes = markdown::extract_rust_codes(res_code);
+
+    match codes.len() {
+        0 => res_code.to_string(),
+        _ => codes.join("\n"),
+    }
+}
+
+fn file_content2token_stream(res_code: &str) -> TokenStream {
+    let res_code = extract_rust_code(res_code);
+
+    match res_code.parse() {
+        Ok(ok) => ok,
+        Err(_) => quote::quote! { compile_error!(#res_code); },
+    }
+}
+
+/// APIキーを環境変数 (あるいは `.env` ファイル) から読み込む
+///
+/// 環境変数名は設定ファイルの `api_key_env` で変更できる (デフォルトは `OPENAI_API_KEY` )
+pub fn api_key(config: &Config) -> eyre::Result<String> {
+    dotenvy::dotenv().ok();
+
+    let name = config.api_key_env();
+    std::env::var(name).map_err(|e| eyre::eyre!("{}: {}", name, e))
+}
+
+fn make_setting(
+    input: &MacroInput,
+    config: &Config,
+    api_key: String,
+    model: String,
+    prompt: &Prompt,
+) -> QuerySetting {
+    // なるべく冪等に近づけるために、seedが指定されていない場合はハッシュを指定
+    let seed = input
+        .seed
+        .or(config.seed)
+        .unwrap_or_else(|| hash_content(&prompt.dncl_code()));
+
+    QuerySetting {
+        api_key,
+        model,
+        seed,
+        max_completion_tokens: input.max_completion_tokens.or(config.max_completion_tokens),
+        base_url: config
+            .base_url
+            .as_ref()
+            .map(|_| config.base_url().to_string()),
+        sampling: input.sampling.clone().or(config.sampling()),
+        best_of: input.best_of.or(config.best_of),
+        cache_dir: config.cache_dir(),
+    }
+}
+
+/// マクロ呼び出しに対応するキャッシュファイル名を求める
+///
+/// モデルの存在チェックは行わないため、APIを叩かずに計算できる。 `@pin` が指定されている場合はそのファイル名になる
+pub fn cache_file_name(input: &MacroInput, config: &Config, api_key: &str) -> eyre::Result<String> {
+    if let Some(pin) = &input.pin {
+        return Ok(cache::pinned_file_name(&pin.value()));
+    }
+
+    let prompt = Prompt::new(
+        &input.dncl_source(),
+        config,
+        input.semantics.unwrap_or(config.semantics),
+        input.dialect.unwrap_or(config.dialect),
+        input.index_base.or(config.index_base),
+        &input.extras(),
+    )?;
+    let model = resolve_model(input.model.as_ref(), config);
+    let setting = make_setting(input, config, api_key.to_string(), model, &prompt);
+    let cache_key = prompt.cache_key();
+
+    if setting.load_cache(&cache_key)?.is_none() {
+        if let Some((setting, cache_key, _)) = find_alternative_cache(input, config, &setting)? {
+            return Ok(cache::cache_file_name(&setting, &cache_key));
+        }
+    }
+
+    Ok(cache::cache_file_name(&setting, &cache_key))
+}
+
+/// 現在の方式のキャッシュがない場合に、別の方式 ( [`MacroInput::alternative_dncl_sources`] ) で組み立てたDNCLソースコードのキャッシュを探す
+///
+/// 見つかった場合はそのクエリ設定・キャッシュキーとキャッシュを返す
+fn find_alternative_cache(
+    input: &MacroInput,
+    config: &Config,
+    setting: &QuerySetting,
+) -> eyre::Result<Option<(QuerySetting, String, CachedContent)>> {
+    for dncl_source in input.alternative_dncl_sources() {
+        let prompt = Prompt::new(
+            &dncl_source,
+            config,
+            input.semantics.unwrap_or(config.semantics),
+            input.dialect.unwrap_or(config.dialect),
+            input.index_base.or(config.index_base),
+            &input.extras(),
+        )?;
+        let alternative = make_setting(
+            input,
+            config,
+            setting.api_key.clone(),
+            setting.model.clone(),
+            &prompt,
+        );
+        let cache_key = prompt.cache_key();
+
+        if let Some(cache) = alternative.load_cache(&cache_key)? {
+            return Ok(Some((alternative, cache_key, cache)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// 返答が満たすべき条件
+fn requirements(input: &MacroInput, config: &Config) -> candidate::Requirements {
+    let dialect = input.dialect.unwrap_or(config.dialect);
+
+    candidate::Requirements {
+        allowed_deps: input.allow_deps.clone(),
+        // `Cargo.toml` が読めない場合のエラーは展開時に出す
+        declared_crates: deps::manifest_crates(&config.manifest_dir).ok().flatten(),
+        allowed: input.allow.clone(),
+        ..candidate::Requirements::new(
+            &legacy::analyzed_source(&input.dncl_source(), dialect),
+            input.semantics.unwrap_or(config.semantics),
+            input.index_base.or(config.index_base),
+        )
+    }
+}
+
+/// トランスパイル結果
+#[derive(Debug)]
+struct Transpiled {
+    response: String,
+    /// 採用した返答がチェックを通らなかった場合の理由
+    check_error: Option<String>,
+}
+
+impl From<CachedContent> for Transpiled {
+    fn from(cache: CachedContent) -> Self {
+        Transpiled {
+            response: cache.response,
+            check_error: cache.check_error,
+        }
+    }
+}
+
+fn transpile(input: MacroInput) -> syn::Result<Transpiled> {
+    // 編集中の場合は最小限のコードを返す
+    if input.editing {
+        return Ok(Transpiled {
+            response: "fn main() {}".to_string(),
+            check_error: None,
+        });
+    }
+
+    // エラー用Span
+    let span = input.dncl_code.span();
+
+    // 設定ファイル読み込み
+    let config = Config::load_manifest().into_syn(span)?;
+
+    // キャッシュが固定されている場合はAPIを叩かずにそれを使う
+    if let Some(pin) = &input.pin {
+        let cache = cache::load_pinned(&config.cache_dir(), &pin.value()).into_syn(pin.span())?;
+        return Ok(cache.into());
+    }
+
+    // ソースコード部分の処理
+    let dncl_source = input.dncl_source();
+    let index_base = input.index_base.or(config.index_base);
+    let semantics = input.semantics.unwrap_or(config.semantics);
+    let dialect = input.dialect.unwrap_or(config.dialect);
+    let prompt = Prompt::new(
+        &dncl_source,
+        &config,
+        semantics,
+        dialect,
+        index_base,
+        &input.extras(),
+    )
+    .into_syn(span)?;
+    let mut cache_key = prompt.cache_key();
+
+    let offline = input.offline.unwrap_or(config.offline);
+    let emit_rs = input.emit_rs.unwrap_or(config.emit_rs);
+    let regenerate = input.regenerate;
+
+    // 環境変数読み込み
+    let api_key = api_key(&config).into_syn(span)?;
+
+    // モデルが存在するかチェック (オフラインの場合はモデル一覧も取得しない)
+    let check_model = !offline && input.check_model.unwrap_or(config.check_model());
+    let model = check_available(&api_key, input.model.as_ref(), &config, check_model)?;
+
+    let mut setting = make_setting(&input, &config, api_key, model, &prompt);
+
+    // サンプリングパラメータがプロバイダ・モデルに対応しているかチェック
+    setting
+        .sampling
+        .validate(config.provider, &setting.model)
+        .into_syn(span)?;
+
+    // 現在の方式のキャッシュがなければ、以前の方式のキャッシュを使う (再生成する場合は現在の方式で作り直す)
+    let mut cache = setting.load_cache(&cache_key).into_syn(span)?;
+    if cache.is_none() && !regenerate {
+        if let Some((alternative, alternative_key, alternative_cache)) =
+            find_alternative_cache(&input, &config, &setting).into_syn(span)?
+        {
+            setting = alternative;
+            cache_key = alternative_key;
+            cache = Some(alternative_cache);
+        }
+    }
+
+    // キャッシュがあるならクエリしない
+    match cache {
+        // 再生成が要求されていてまだ再生成していない場合はクエリし直す
+        Some(cache) if regenerate && !cache.regenerated && !offline => {}
+        // 再生成の要求が外されたら印を外し、次の `@regenerate = true` で再び再生成できるようにする
+        Some(cache) if !regenerate && cache.regenerated => {
+            let transpiled = Transpiled {
+                response: cache.response.clone(),
+                check_error: cache.check_error.clone(),
+            };
+            setting
+                .mark_regenerated(&cache_key, cache, false)
+                .into_syn(span)?;
+            return Ok(transpiled);
+        }
+        Some(cache) => return Ok(cache.into()),
+        None => {}
+    }
+
+    if offline {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "No cached response ({}) is found in offline mode",
+                cache::cache_file_name(&setting, &cache_key)
+            ),
+        ));
+    }
+
+    // トランスパイルクエリ部分
+    let requirements = requirements(&input, &config);
+    let response =
+        candidate::generate(&setting, &prompt.messages(), &requirements).into_syn(span)?;
+
+    // 再生成の場合、古いキャッシュは履歴として残す
+    if regenerate {
+        setting.archive_cache(&cache_key).into_syn(span)?;
+    }
+
+    // 返答をキャッシュへ保存
+    setting
+        .save_cache(&cache_key, &dncl_source, &response, emit_rs, regenerate)
+        .into_syn(span)?;
+
+    Ok(Transpiled {
+        response: response.response,
+        check_error: response.check_error,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use proc_macro2::Span;
+    use syn::LitStr;
+
+    use super::{check_expanded, transpile, MacroInput};
+    use crate::candidate::Requirements;
+    use crate::config::Semantics;
+    use crate::security::Capability;
+
+    impl From<String> for MacroInput {
+        fn from(value: String) -> Self {
+            MacroInput {
+                model: None,
+                seed: None,
+                max_completion_tokens: None,
+                sampling: Default::default(),
+                best_of: None,
+                prompt_extras: Vec::new(),
+                functions: Default::default(),
+                index_base: None,
+                semantics: None,
+                dialect: None,
+                inputs_script: None,
+                rng_seed: None,
+                allow_deps: None,
+                allow: Vec::new(),
+                lint: true,
+                check_model: None,
+                offline: None,
+                editing: false,
+                emit_rs: None,
+                regenerate: false,
+                pin: None,
+                dncl_code: value.parse().unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_expanded() {
+        let none = Requirements::default();
+
+        assert!(check_expanded("fn main() { println!(\"{}\", 1); }", &none).is_ok());
+        // パースできないコードは確認できないので展開しない
+        assert!(
+            check_expanded("fn main( { std::fs::remove_dir_all(\"/\") }", &none)
+                .unwrap_err()
+                .contains("cannot be parsed")
+        );
+        assert!(check_expanded(
+            "use std as s;\nfn main() { s::fs::read(\"a\").ok(); }",
+            &none
+        )
+        .unwrap_err()
+        .contains("@allow = [\"fs\"]"));
+
+        let fs = Requirements {
+            allowed: vec![Capability::Fs],
+            ..Requirements::default()
+        };
+        assert!(check_expanded("fn main() { std::fs::read(\"a\").ok(); }", &fs).is_ok());
+
+        // `@index_base` は候補の選択で確認し、満たさなくても警告にとどめる
+        let index = Requirements::new("表示する(Data[1])", Semantics::Static, Some(1));
+        assert!(check_expanded(
+            "fn main() { let data = [0, 1]; let i = 1; data[i]; }",
+            &index
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_1() {
+        let code = r#"
+表示する("Hello, world!");
+"#;
+
+        let macro_input = MacroInput {
+            model: None,
+            seed: None,
+            max_completion_tokens: None,
+            sampling: Default::default(),
+            best_of: None,
+            prompt_extras: Vec::new(),
+            functions: Default::default(),
+            index_base: None,
+            semantics: None,
+            dialect: None,
+            inputs_script: None,
+            rng_seed: None,
+            allow_deps: None,
+            allow: Vec::new(),
+            lint: true,
+            check_model: None,
+            offline: None,
+            editing: false,
+            emit_rs: None,
+            regenerate: false,
+            pin: None,
+            dncl_code: code.to_string().parse().unwrap(),
+        };
+
+        let res = transpile(macro_input).unwrap();
+
+        dbg!(res);
+    }
+
+    #[test]
+    fn test_2() {
+        let code = r#"
+Data=[3,18,29,33,48,52,62,77,89,97];
+kazu=要素数(Data);
+表示する("0~99の数字を入力してください");
+atai={外部からの入力};
+hidari=0, migi=kazu-1;
+owari=0;
+hidari <= migi and owari == 0 の間繰り返す: ;
+|  aida=(hidari+migi) div 2 # 演算子 div は商の整数値を返す;
+|  もし Data[aida] == atai ならば: ;
+|  |  表示する(atai,"は",aida,"番目にありました");
+|  |  owari=1;
+|  そうでなくもし Data[aida]<atai ならば: ;
+|  |  hidari=aida+1;
+|  そうでなければ: ;
+|= |= migi=aida-1;
+もし owari==0 ならば: ;
+|= 表示する(atai,"は見つかりませんでした");
+表示する("添字"," ","要素");
+iを0からkazu-1まで1ずつ増やしながら繰り返す: ;
+|= 表示する(i," ",Data[i]);
+"#;
+
+        let macro_input = MacroInput {
+            model: Some(LitStr::new("o1-preview", Span::call_site())),
+            seed: None,
+            max_completion_tokens: None,
+            sampling: Default::default(),
+            best_of: None,
+            prompt_extras: Vec::new(),
+            functions: Default::default(),
+            index_base: None,
+            semantics: None,
+            dialect: None,
+            inputs_script: None,
+            rng_seed: None,
+            allow_deps: None,
+            allow: Vec::new(),
+            lint: true,
+            check_model: None,
+            offline: None,
+            editing: false,
+            emit_rs: None,
+            regenerate: false,
+            pin: None,
+            dncl_code: code.to_string().parse().unwrap(),
+        };
+
+        let res = transpile(macro_input).unwrap();
+
+        dbg!(res);
+    }
+}
@@ -0,0 +1,667 @@
+//! DNCLの仕様上の約束事のチェック
+//!
+//! トランスパイル結果には影響しないが、問題のコードとして誤りの可能性が高い箇所を警告する。
+//! マクロでは `#[deprecated]` を使ってコンパイラの警告として表示し、 `cargo dncl lint` でも表示する
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use proc_macro2::{Span, TokenStream};
+
+use crate::config::Dialect;
+use crate::infer::{infer, split_assignment, split_statements, Ty};
+use crate::legacy::analyzed_source;
+use crate::normalize::normalize;
+use crate::source::{lines, Line};
+use crate::token::{tokenize, Kind, Token};
+
+/// 警告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// DNCLコードの中の行番号 (1始まり)
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// 式の中で変数として使われている名前 (関数呼び出しの名前を除く)
+fn used_vars(text: &str) -> Vec<&str> {
+    let tokens = tokenize(text);
+
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, token)| {
+            token.kind == Kind::Ident
+                && tokens.get(i + 1).map(|next| next.kind) != Some(Kind::Open('('))
+        })
+        .map(|(_, token)| token.text)
+        .collect()
+}
+
+/// 添字でアクセスされている名前
+fn indexed_names(text: &str) -> Vec<&str> {
+    let tokens = tokenize(text);
+
+    tokens
+        .windows(2)
+        .filter(|pair| pair[0].kind == Kind::Ident && pair[1].kind == Kind::Open('['))
+        .map(|pair| pair[0].text)
+        .collect()
+}
+
+/// 比較を `(左辺, 演算子, 右辺)` に分ける
+fn comparison(cond: &str) -> Option<(String, &str, String)> {
+    let tokens = tokenize(cond);
+    let i = tokens.iter().position(|token| {
+        token.kind == Kind::Op && matches!(token.text, "==" | "!=" | "<=" | ">=" | "<" | ">")
+    })?;
+    let join = |tokens: &[Token]| {
+        tokens
+            .iter()
+            .map(|token| token.text)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    Some((join(&tokens[..i]), tokens[i].text, join(&tokens[i + 1..])))
+}
+
+/// 比較を否定した演算子
+fn negated(op: &str) -> &str {
+    match op {
+        "==" => "!=",
+        "!=" => "==",
+        "<" => ">=",
+        ">=" => "<",
+        ">" => "<=",
+        _ => ">",
+    }
+}
+
+/// 数値どうしの比較など、常に成り立つ条件か
+fn always_true(cond: &str) -> bool {
+    let Some((lhs, op, rhs)) = comparison(cond) else {
+        return false;
+    };
+    let (Ok(lhs), Ok(rhs)) = (lhs.parse::<f64>(), rhs.parse::<f64>()) else {
+        return false;
+    };
+
+    match op {
+        "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        "<=" => lhs <= rhs,
+        ">" => lhs > rhs,
+        _ => lhs >= rhs,
+    }
+}
+
+/// `もし … ならば:` から続く条件分岐
+struct Chain {
+    depth: usize,
+    /// これまでの条件とその行番号
+    conditions: Vec<(usize, String)>,
+}
+
+impl Chain {
+    /// これまでの条件のいずれかが必ず成り立つ場合、その理由
+    fn exhausted(&self) -> Option<String> {
+        for (i, (line, cond)) in self.conditions.iter().enumerate() {
+            if always_true(cond) {
+                return Some(format!(
+                    "the condition `{}` on line {} is always true",
+                    cond, line
+                ));
+            }
+            let Some((lhs, op, rhs)) = comparison(cond) else {
+                continue;
+            };
+            for (other_line, other) in &self.conditions[..i] {
+                if comparison(other) == Some((lhs.clone(), negated(op), rhs.clone())) {
+                    return Some(format!(
+                        "the conditions `{}` on line {} and `{}` on line {} cover every case",
+                        other, other_line, cond, line
+                    ));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+struct Linter {
+    warnings: Vec<Warning>,
+    /// 代入された (あるいは関数の引数・繰り返しの変数として導入された) 名前
+    defined: BTreeSet<String>,
+    /// 配列として使われている名前と、最初に使われた行
+    arrays: Vec<(String, usize)>,
+    /// 配列でない値を代入された名前と、最初に代入された行
+    scalars: Vec<(String, usize)>,
+    /// 未定義の変数として警告済みの名前
+    reported: BTreeSet<String>,
+    chains: Vec<Chain>,
+}
+
+impl Linter {
+    fn warn(&mut self, line: usize, message: String) {
+        self.warnings.push(Warning { line, message });
+    }
+
+    fn array(&mut self, name: &str, line: usize) {
+        if !self.arrays.iter().any(|(array, _)| array == name) {
+            self.arrays.push((name.to_string(), line));
+        }
+    }
+
+    fn scalar(&mut self, name: &str, line: usize) {
+        if !self.scalars.iter().any(|(scalar, _)| scalar == name) {
+            self.scalars.push((name.to_string(), line));
+        }
+    }
+
+    /// 変数が代入より前に使われていないか確認する
+    ///
+    /// 配列 (大文字で始まる名前) は問題文で与えられることが多いため対象外とする
+    fn use_vars(&mut self, text: &str, line: usize) {
+        for name in indexed_names(text) {
+            self.array(name, line);
+        }
+        for name in used_vars(text) {
+            if name.starts_with(|c: char| c.is_ascii_uppercase())
+                || self.defined.contains(name)
+                || self.reported.contains(name)
+            {
+                continue;
+            }
+            self.reported.insert(name.to_string());
+            self.warn(
+                line,
+                format!("variable `{}` is used before it is assigned", name),
+            );
+        }
+    }
+
+    /// 整数と推論された変数のうち、配列でないもの
+    fn defined_ints(&self, ints: &BTreeSet<String>) -> BTreeSet<String> {
+        ints.iter()
+            .filter(|name| !self.arrays.iter().any(|(array, _)| array == *name))
+            .cloned()
+            .collect()
+    }
+
+    fn define(&mut self, name: &str) {
+        self.defined.insert(name.to_string());
+    }
+
+    /// 条件分岐の続きを確認し、終わった分岐を取り除く
+    fn branch(&mut self, line: usize, depth: usize, text: &str) {
+        self.chains.retain(|chain| chain.depth < depth);
+
+        if let Some(cond) = text
+            .strip_prefix("もし")
+            .and_then(|rest| rest.strip_suffix("ならば:"))
+        {
+            self.chains.push(Chain {
+                depth,
+                conditions: vec![(line, cond.trim().to_string())],
+            });
+        }
+    }
+
+    fn continuation(&mut self, line: usize, depth: usize, text: &str) {
+        let Some(chain) = self
+            .chains
+            .iter_mut()
+            .rev()
+            .find(|chain| chain.depth == depth)
+        else {
+            return;
+        };
+        let reason = chain.exhausted();
+
+        match text
+            .strip_prefix("そうでなくもし")
+            .and_then(|rest| rest.strip_suffix("ならば:"))
+        {
+            Some(cond) => {
+                let cond = cond.trim().to_string();
+                let duplicate = chain
+                    .conditions
+                    .iter()
+                    .find(|(_, other)| *other == cond)
+                    .map(|(other_line, _)| {
+                        format!("the same condition is checked on line {}", other_line)
+                    });
+                chain.conditions.push((line, cond));
+
+                if let Some(reason) = reason.or(duplicate) {
+                    self.warn(line, format!("unreachable branch: {}", reason));
+                }
+            }
+            None => {
+                if let Some(reason) = reason {
+                    self.warn(line, format!("unreachable else branch: {}", reason));
+                }
+            }
+        }
+    }
+
+    /// `i を a から b まで s ずつ増やしながら繰り返す:`
+    fn for_loop(&mut self, line: usize, text: &str) {
+        let Some((var, rest)) = text.split_once('を') else {
+            return;
+        };
+        let increasing = text.contains("ずつ増やしながら");
+        let range = rest.split_once("から").and_then(|(start, rest)| {
+            let (end, rest) = rest.split_once("まで")?;
+            let (step, _) = rest.split_once("ずつ")?;
+            Some((start.trim(), end.trim(), step.trim()))
+        });
+
+        self.use_vars(rest, line);
+        self.define(var.trim());
+        self.scalar(var.trim(), line);
+
+        let Some((start, end, step)) = range else {
+            return;
+        };
+        let number = |s: &str| s.parse::<f64>().ok();
+        let direction = match increasing {
+            true => "増やしながら",
+            false => "減らしながら",
+        };
+
+        match (number(start), number(end), number(step)) {
+            (_, _, Some(0.0)) => {
+                self.warn(line, "the loop step is 0, so the loop never terminates".to_string())
+            }
+            (_, _, Some(step)) if step < 0.0 => self.warn(
+                line,
+                format!(
+                    "the loop step {} is negative with {}, so the counter moves away from the end; use a positive step",
+                    step, direction
+                ),
+            ),
+            (Some(start), Some(end), _) if (increasing && start > end) || (!increasing && start < end) => {
+                self.warn(
+                    line,
+                    format!(
+                        "the loop goes from {} to {} with {}, so its body never runs",
+                        start, end, direction
+                    ),
+                )
+            }
+            _ => {}
+        }
+    }
+
+    /// 代入文などの1行
+    fn statement(&mut self, line: usize, text: &str) {
+        // `Tokuten のすべての値を 0 にする`
+        if let Some((name, value)) = text.split_once("のすべての値を") {
+            self.use_vars(value, line);
+            self.use_vars(name, line);
+            self.array(name.trim(), line);
+            return;
+        }
+
+        for statement in split_statements(text) {
+            let Some((lhs, rhs)) = split_assignment(statement) else {
+                self.use_vars(statement, line);
+                continue;
+            };
+            self.use_vars(rhs, line);
+
+            match lhs.split_once('[') {
+                Some((name, index)) => {
+                    self.use_vars(index, line);
+                    self.array(name.trim(), line);
+                    self.define(name.trim());
+                }
+                None => {
+                    if rhs.starts_with('[') {
+                        self.array(lhs, line);
+                    } else {
+                        self.scalar(lhs, line);
+                    }
+                    self.define(lhs);
+                }
+            }
+        }
+    }
+
+    /// `C の間繰り返す:` の本体で条件の変数が変わらないか確認する
+    fn while_loop(&mut self, line: usize, cond: &str, body: &[(usize, Line)]) {
+        let vars = used_vars(cond);
+        if vars.is_empty() {
+            return;
+        }
+
+        let changed = body.iter().any(|(_, Line { text, .. })| {
+            let assigned = split_statements(text)
+                .iter()
+                .filter_map(|statement| split_assignment(statement))
+                .map(|(lhs, _)| lhs.split('[').next().unwrap_or(lhs).trim().to_string())
+                .chain(text.split_once('を').map(|(var, _)| var.trim().to_string()))
+                .chain(
+                    text.split_once("のすべての値を")
+                        .map(|(name, _)| name.trim().to_string()),
+                )
+                .collect::<Vec<_>>();
+            // 関数呼び出しの中で変わる可能性がある場合は警告しない
+            let calls = tokenize(text)
+                .windows(2)
+                .any(|pair| pair[0].kind == Kind::Ident && pair[1].kind == Kind::Open('('));
+
+            calls
+                || vars
+                    .iter()
+                    .any(|var| assigned.iter().any(|name| name == var))
+        });
+
+        if !changed {
+            self.warn(
+                line,
+                format!(
+                    "no variable in the loop condition `{}` changes in its body, so the loop never terminates once entered",
+                    cond
+                ),
+            );
+        }
+    }
+}
+
+/// 実数の除算 `/` の両辺が整数か
+fn integer_division(text: &str, ints: &BTreeSet<String>) -> Option<String> {
+    let tokens = tokenize(text);
+    let is_int = |token: Option<&Token>| match token {
+        Some(token) if token.kind == Kind::Num => !token.text.contains('.'),
+        Some(token) if token.kind == Kind::Ident => ints.contains(token.text),
+        _ => false,
+    };
+
+    (1..tokens.len()).find_map(|i| {
+        let operands_are_ints = tokens[i].is(Kind::Op, "/")
+            && is_int(tokens.get(i - 1))
+            && is_int(tokens.get(i + 1))
+            && !tokens
+                .get(i + 2)
+                .is_some_and(|token| matches!(token.kind, Kind::Open(_)));
+
+        operands_are_ints.then(|| format!("{} / {}", tokens[i - 1].text, tokens[i + 1].text))
+    })
+}
+
+/// DNCLコードの約束事をチェックする
+///
+/// 行番号は与えられたコードの行 (空行を含む) で数える
+pub fn lint(dncl_source: &str, dialect: Dialect) -> Vec<Warning> {
+    // 行ごとに正規化・R7の文法への書き換えを行い、元の行番号を保つ
+    let lines = dncl_source
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let normalized = normalize(line);
+            lines(&analyzed_source(&normalized, dialect))
+                .into_iter()
+                .map(move |line| (i + 1, line))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let analyzed = lines
+        .iter()
+        .map(|(_, Line { text, .. })| text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let ints = infer(&analyzed)
+        .into_iter()
+        .filter(|var| var.ty == Ty::Int)
+        .map(|var| var.name)
+        .collect::<BTreeSet<_>>();
+    let uses_int_quotient = lines.iter().any(|(_, Line { text, .. })| {
+        tokenize(text)
+            .iter()
+            .any(|token| token.is(Kind::Op, "÷") || token.is(Kind::Op, "div"))
+    });
+
+    let mut linter = Linter {
+        warnings: Vec::new(),
+        defined: BTreeSet::new(),
+        arrays: Vec::new(),
+        scalars: Vec::new(),
+        reported: BTreeSet::new(),
+        chains: Vec::new(),
+    };
+
+    for (i, (line, Line { depth, text })) in lines.iter().enumerate() {
+        let (line, depth) = (*line, *depth);
+
+        if text.starts_with("そうでなければ") || text.starts_with("そうでなくもし") {
+            linter.continuation(line, depth, text);
+            if let Some(cond) = text
+                .strip_prefix("そうでなくもし")
+                .and_then(|rest| rest.strip_suffix("ならば:"))
+            {
+                linter.use_vars(cond, line);
+            }
+            continue;
+        }
+        linter.branch(line, depth, text);
+
+        if uses_int_quotient {
+            if let Some(division) = integer_division(text, &linter.defined_ints(&ints)) {
+                linter.warn(
+                    line,
+                    format!(
+                        "`{}` divides integers with `/`, which gives a real number, while `÷` is used elsewhere for the integer quotient",
+                        division
+                    ),
+                );
+            }
+        }
+
+        if let Some(rest) = text.strip_prefix("関数") {
+            // `関数 name(a, b) を 定義する:`
+            if let Some((_, params)) = rest.split_once('(') {
+                let params = params.split(')').next().unwrap_or_default();
+                for param in params.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                    linter.define(param);
+                }
+            }
+        } else if let Some(cond) = text
+            .strip_prefix("もし")
+            .and_then(|rest| rest.strip_suffix("ならば:"))
+        {
+            linter.use_vars(cond, line);
+        } else if let Some(cond) = text.strip_suffix("の間繰り返す:") {
+            let body_len = lines[i + 1..]
+                .iter()
+                .take_while(|(_, body)| body.depth > depth)
+                .count();
+            linter.use_vars(cond, line);
+            linter.while_loop(line, cond.trim(), &lines[i + 1..i + 1 + body_len]);
+        } else if text.contains("ずつ増やしながら") || text.contains("ずつ減らしながら")
+        {
+            linter.for_loop(line, text);
+        } else if let Some(value) = text.strip_suffix("を返す") {
+            linter.use_vars(value, line);
+        } else {
+            linter.statement(line, text);
+        }
+    }
+
+    for (name, line) in linter.arrays.clone() {
+        if name.starts_with(|c: char| c.is_ascii_lowercase()) {
+            linter.warn(
+                line,
+                format!("array `{}` should start with an uppercase letter", name),
+            );
+        }
+    }
+    for (name, line) in linter.scalars.clone() {
+        let is_array = linter.arrays.iter().any(|(array, _)| *array == name);
+        if !is_array && name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            linter.warn(
+                line,
+                format!(
+                    "variable `{}` should start with a lowercase letter (only array names start with an uppercase letter)",
+                    name
+                ),
+            );
+        }
+    }
+
+    linter.warnings.sort_by_key(|warning| warning.line);
+    linter.warnings
+}
+
+/// 警告をコンパイラの警告として表示させるコード
+///
+/// 安定版のproc-macroでは警告を直接出せないため、 `#[deprecated]` な項目を使って警告させる
+pub(crate) fn warning_tokens(warnings: &[Warning], span: Span) -> TokenStream {
+    warnings
+        .iter()
+        .map(|warning| note_tokens("DnclLint", &format!("DNCL {}", warning), span))
+        .collect()
+}
+
+/// `note` を `ident` という名前の `#[deprecated]` な構造体の警告として表示させるコード
+pub(crate) fn note_tokens(ident: &str, note: &str, span: Span) -> TokenStream {
+    let ident = syn::Ident::new(ident, span);
+
+    quote::quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #note)]
+            struct #ident;
+            let _ = #ident;
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proc_macro2::Span;
+
+    use super::{lint, note_tokens, Warning};
+    use crate::config::Dialect;
+
+    fn messages(source: &str) -> Vec<String> {
+        lint(source, Dialect::R7)
+            .into_iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_clean() {
+        let source = r#"
+Data = [3, 1, 4]
+goukei = 0
+i を 0 から 要素数(Data) - 1 まで 1 ずつ増やしながら繰り返す:
+│ もし Data[i] > 2 ならば:
+│ │ goukei = goukei + Data[i]
+│ そうでなければ:
+│ └ goukei = goukei - 1
+heikin = goukei / 要素数(Data)
+n = 0
+n < 3 の間繰り返す:
+└ n = n + 1
+jを1から3まで1ずつ増やしながら繰り返す:
+└ 表示する(j)
+表示する(goukei ÷ 2, heikin, Akibi[0])
+"#;
+
+        assert_eq!(messages(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_lint() {
+        let source = r#"
+data = [1, 2]
+Kosu = 3
+表示する(kekka)
+もし Kosu >= 3 ならば:
+│ 表示する("3以上")
+そうでなくもし Kosu < 3 ならば:
+│ 表示する("3未満")
+そうでなければ:
+└ 表示する("なし")
+i を 10 から 1 まで 1 ずつ増やしながら繰り返す:
+└ 表示する(i)
+n = 0
+n < 10 の間繰り返す:
+└ 表示する(n)
+heikin = Kosu / 2, amari = Kosu ÷ 2
+"#;
+
+        assert_eq!(
+            lint(source, Dialect::R7),
+            vec![
+                Warning {
+                    line: 2,
+                    message: "array `data` should start with an uppercase letter".to_string()
+                },
+                Warning {
+                    line: 3,
+                    message: "variable `Kosu` should start with a lowercase letter (only array names start with an uppercase letter)".to_string()
+                },
+                Warning {
+                    line: 4,
+                    message: "variable `kekka` is used before it is assigned".to_string()
+                },
+                Warning {
+                    line: 9,
+                    message: "unreachable else branch: the conditions `Kosu >= 3` on line 5 and `Kosu < 3` on line 7 cover every case".to_string()
+                },
+                Warning {
+                    line: 11,
+                    message: "the loop goes from 10 to 1 with 増やしながら, so its body never runs".to_string()
+                },
+                Warning {
+                    line: 14,
+                    message: "no variable in the loop condition `n < 10` changes in its body, so the loop never terminates once entered".to_string()
+                },
+                Warning {
+                    line: 16,
+                    message: "`Kosu / 2` divides integers with `/`, which gives a real number, while `÷` is used elsewhere for the integer quotient".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_legacy() {
+        let source = "x ← 0\nx < 10 の間,\n│ 「x は」と x を表示する\n└ を繰り返す";
+
+        assert_eq!(
+            lint(source, Dialect::Legacy)
+                .into_iter()
+                .map(|warning| warning.line)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn test_note_tokens() {
+        let tokens = note_tokens(
+            "DnclCheck",
+            "generated code did not pass",
+            Span::call_site(),
+        );
+        let code = tokens.to_string();
+
+        assert!(code.contains("deprecated"));
+        assert!(code.contains("\"generated code did not pass\""));
+        assert!(syn::parse2::<syn::ItemConst>(tokens).is_ok());
+    }
+}
@@ -0,0 +1,443 @@
+use std::fs;
+
+use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
+use syn::{bracketed, punctuated::Punctuated, Ident, Lit, LitBool, LitInt, LitStr};
+use syn::{
+    parse::{Parse, ParseStream},
+    Token,
+};
+
+use crate::config::{Config, Dialect, Semantics};
+use crate::deps;
+use crate::functions::Functions;
+use crate::lint;
+use crate::normalize;
+use crate::sampling::Sampling;
+use crate::security::Capability;
+use crate::source;
+
+pub struct MacroInput {
+    pub model: Option<LitStr>,
+    pub seed: Option<i64>,
+    pub max_completion_tokens: Option<u32>,
+    pub sampling: Sampling,
+    pub best_of: Option<u32>,
+    /// `@prompt_extra` / `@prompt_file` で与えられた問題固有の説明 (指定順)
+    pub prompt_extras: Vec<String>,
+    /// `@functions` で宣言された問題固有の関数
+    pub functions: Functions,
+    /// 配列の添字の開始番号
+    pub index_base: Option<u32>,
+    /// 生成するRustコードでのDNCLの値の扱い
+    pub semantics: Option<Semantics>,
+    /// DNCLの文法 (R7の仕様か、旧仕様か)
+    pub dialect: Option<Dialect>,
+    /// `{外部からの入力}` で順に読み込まれる入力
+    pub inputs_script: Option<Vec<String>>,
+    /// `乱数()` のシード値
+    pub rng_seed: Option<u64>,
+    /// 生成コードで使用を許可する外部クレート
+    pub allow_deps: Option<Vec<String>>,
+    /// 生成コードで許可する操作 ( `unsafe` や `std::fs` など)
+    pub allow: Vec<Capability>,
+    /// DNCLの約束事のチェック ( [`crate::lint`] ) の警告を出すか
+    pub lint: bool,
+    pub check_model: Option<bool>,
+    pub offline: Option<bool>,
+    pub editing: bool,
+    pub emit_rs: Option<bool>,
+    pub regenerate: bool,
+    pub pin: Option<LitStr>,
+    pub dncl_code: TokenStream,
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut model: Option<LitStr> = None;
+        let mut seed: Option<i64> = None;
+        let mut max_completion_tokens: Option<u32> = None;
+        let mut sampling = Sampling::default();
+        let mut best_of: Option<u32> = None;
+        let mut prompt_extras: Vec<String> = Vec::new();
+        let mut functions = Functions::default();
+        let mut index_base: Option<u32> = None;
+        let mut semantics: Option<Semantics> = None;
+        let mut dialect: Option<Dialect> = None;
+        let mut inputs_script: Option<Vec<String>> = None;
+        let mut rng_seed: Option<u64> = None;
+        let mut allow_deps: Option<Vec<String>> = None;
+        let mut allow: Vec<Capability> = Vec::new();
+        let mut file_content: Option<String> = None;
+        let mut lint = true;
+        let mut check_model: Option<bool> = None;
+        let mut offline: Option<bool> = None;
+        let mut editing = false;
+        let mut emit_rs: Option<bool> = None;
+        let mut regenerate = false;
+        let mut pin: Option<LitStr> = None;
+
+        while input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            let ident = input.parse::<Ident>()?;
+            // `@functions { ... }` のみ `=` を省略できる
+            if !(ident == "functions" && input.peek(syn::token::Brace)) {
+                input.parse::<syn::Token![=]>()?;
+            }
+            match ident {
+                i if i == "model" => {
+                    let lit = input.parse::<LitStr>()?;
+                    model = Some(lit);
+                }
+                i if i == "max_completion_tokens" => {
+                    let value = input.parse::<LitInt>()?;
+                    max_completion_tokens = Some(value.base10_parse()?);
+                }
+                i if i == "temperature" => {
+                    sampling.temperature = Some(parse_f64(input)?);
+                }
+                i if i == "top_p" => {
+                    sampling.top_p = Some(parse_f64(input)?);
+                }
+                i if i == "reasoning_effort" => {
+                    let lit = input.parse::<LitStr>()?;
+                    let value = lit.value().parse().into_syn(lit.span())?;
+                    sampling.reasoning_effort = Some(value);
+                }
+                i if i == "stop" => {
+                    sampling.stop = Some(parse_str_or_array(input)?);
+                }
+                i if i == "n" => {
+                    let value = input.parse::<LitInt>()?;
+                    sampling.n = Some(value.base10_parse()?);
+                }
+                i if i == "best_of" => {
+                    let value = input.parse::<LitInt>()?;
+                    best_of = Some(value.base10_parse()?);
+                }
+                i if i == "seed" => {
+                    let value = input.parse::<LitInt>()?;
+                    seed = Some(value.base10_parse()?);
+                }
+                i if i == "file" => {
+                    let value = input.parse::<LitStr>()?;
+                    let file_path = value.value();
+
+                    file_content = Some(fs::read_to_string(file_path).into_syn(value.span())?);
+                }
+                i if i == "prompt_extra" => {
+                    prompt_extras.push(input.parse::<LitStr>()?.value());
+                }
+                i if i == "prompt_file" => {
+                    let value = input.parse::<LitStr>()?;
+                    let file_path = value.value();
+
+                    prompt_extras.push(fs::read_to_string(file_path).into_syn(value.span())?);
+                }
+                i if i == "functions" => {
+                    functions = input.parse::<Functions>()?;
+                }
+                i if i == "index_base" => {
+                    index_base = Some(input.parse::<LitInt>()?.base10_parse()?);
+                }
+                i if i == "semantics" => {
+                    let lit = input.parse::<LitStr>()?;
+                    let value = lit.value().parse().into_syn(lit.span())?;
+                    semantics = Some(value);
+                }
+                i if i == "dialect" => {
+                    let lit = input.parse::<LitStr>()?;
+                    let value = lit.value().parse().into_syn(lit.span())?;
+                    dialect = Some(value);
+                }
+                i if i == "inputs_script" => {
+                    inputs_script = Some(parse_str_or_array(input)?);
+                }
+                i if i == "rng_seed" => {
+                    rng_seed = Some(input.parse::<LitInt>()?.base10_parse()?);
+                }
+                i if i == "allow_deps" => {
+                    allow_deps = Some(parse_str_or_array(input)?);
+                }
+                i if i == "allow" => {
+                    let span = input.span();
+                    allow = parse_str_or_array(input)?
+                        .iter()
+                        .map(|name| name.parse())
+                        .collect::<eyre::Result<_>>()
+                        .into_syn(span)?;
+                }
+                i if i == "lint" => {
+                    lint = input.parse::<LitBool>()?.value;
+                }
+                i if i == "check_model" => {
+                    check_model = Some(input.parse::<LitBool>()?.value);
+                }
+                i if i == "offline" => {
+                    offline = Some(input.parse::<LitBool>()?.value);
+                }
+                i if i == "editing" => {
+                    editing = input.parse::<LitBool>()?.value;
+                }
+                i if i == "emit_rs" => {
+                    emit_rs = Some(input.parse::<LitBool>()?.value);
+                }
+                i if i == "regenerate" => {
+                    regenerate = input.parse::<LitBool>()?.value;
+                }
+                i if i == "pin" => {
+                    pin = Some(input.parse::<LitStr>()?);
+                }
+                _ => return Err(syn::Error::new(ident.span(), "unexpected field")),
+            }
+
+            // `;` , `,` のパース
+            if input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+            } else if input.peek(Token![;]) {
+                input.parse::<Token![;]>()?;
+            }
+        }
+
+        if let (true, Some(pin)) = (regenerate, &pin) {
+            return Err(syn::Error::new(
+                pin.span(),
+                "`@pin` and `@regenerate` cannot be used together",
+            ));
+        }
+
+        let dncl_code: TokenStream = if let Some(file_content) = file_content {
+            if file_content.is_empty() {
+                return Err(syn::Error::new(Span::call_site(), "file is empty"));
+            }
+
+            LitStr::new(&file_content, Span::call_site()).into_token_stream()
+        } else if input.peek(LitStr) {
+            let lit = input.parse::<LitStr>()?;
+
+            if lit.value().is_empty() {
+                return Err(syn::Error::new(lit.span(), "code is empty"));
+            }
+
+            lit.into_token_stream()
+        } else {
+            if input.is_empty() {
+                return Err(syn::Error::new(Span::call_site(), "code is empty"));
+            }
+
+            input.parse::<TokenStream>()?
+        };
+
+        Ok(Self {
+            model,
+            seed,
+            max_completion_tokens,
+            sampling,
+            best_of,
+            prompt_extras,
+            functions,
+            index_base,
+            semantics,
+            dialect,
+            inputs_script,
+            rng_seed,
+            allow_deps,
+            allow,
+            lint,
+            check_model,
+            offline,
+            editing,
+            emit_rs,
+            regenerate,
+            pin,
+            dncl_code,
+        })
+    }
+}
+
+/// `0.5` や `0` のような数値リテラルをパースする
+fn parse_f64(input: ParseStream) -> syn::Result<f64> {
+    match input.parse::<Lit>()? {
+        Lit::Float(lit) => lit.base10_parse(),
+        Lit::Int(lit) => lit.base10_parse(),
+        lit => Err(syn::Error::new(lit.span(), "expected number")),
+    }
+}
+
+/// `"a"` あるいは `["a", "b"]` をパースする
+fn parse_str_or_array(input: ParseStream) -> syn::Result<Vec<String>> {
+    if input.peek(LitStr) {
+        return Ok(vec![input.parse::<LitStr>()?.value()]);
+    }
+
+    let content;
+    bracketed!(content in input);
+    let lits = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+
+    Ok(lits.iter().map(LitStr::value).collect())
+}
+
+impl MacroInput {
+    /// 問題固有の指示 ( `@functions` の説明、 `@allow_deps` 、 `@prompt_extra` ・ `@prompt_file` の順)
+    pub fn extras(&self) -> Vec<String> {
+        self.functions
+            .prompt()
+            .into_iter()
+            .chain(self.allow_deps.as_deref().map(deps::prompt))
+            .chain(self.prompt_extras.iter().cloned())
+            .collect()
+    }
+
+    /// プロンプトやキャッシュに使う、正規化したDNCLソースコード
+    pub fn dncl_source(&self) -> String {
+        normalize::normalize(&self.raw_dncl_source())
+    }
+
+    /// DNCLの約束事のチェック結果 ( `@lint = false` の場合は空)
+    pub fn lint_warnings(&self, config: &Config) -> Vec<lint::Warning> {
+        match self.lint {
+            true => lint::lint(
+                &self.raw_dncl_source(),
+                self.dialect.unwrap_or(config.dialect),
+            ),
+            false => Vec::new(),
+        }
+    }
+
+    /// 文字列リテラル ( `@file` を含む) の場合はその中身をそのまま、直接書かれている場合はトークン列から組み立てる
+    fn raw_dncl_source(&self) -> String {
+        match syn::parse2::<LitStr>(self.dncl_code.clone()) {
+            Ok(lit) => lit.value(),
+            Err(_) => source::from_tokens(self.dncl_code.clone()),
+        }
+    }
+
+    /// 既存のキャッシュを探すための、 [`Self::dncl_source`] 以外のDNCLソースコードの組み立て方
+    ///
+    /// 正規化や、行構造を保つようになる前 ( `;` と改行を互いに置き換えていた) はキャッシュキーが異なる。
+    /// また、トークンの位置が分かるかどうかは環境によって異なるため、位置を使わない場合も含める
+    pub(crate) fn alternative_dncl_sources(&self) -> Vec<String> {
+        let legacy = |tokens: TokenStream| tokens.to_string().replace(";", "\n");
+
+        let mut sources = vec![self.raw_dncl_source()];
+        sources.extend(match syn::parse2::<LitStr>(self.dncl_code.clone()) {
+            // 文字列リテラルは改行を `;` にしていた ( `@file` はそのまま)
+            Ok(lit) => vec![
+                legacy(
+                    LitStr::new(&lit.value().replace("\n", ";"), lit.span()).into_token_stream(),
+                ),
+                legacy(lit.into_token_stream()),
+            ],
+            Err(_) => vec![
+                source::from_tokens_without_locations(self.dncl_code.clone()),
+                legacy(self.dncl_code.clone()),
+            ],
+        });
+
+        let current = self.dncl_source();
+        let mut alternatives: Vec<String> = Vec::new();
+        for source in sources {
+            if source != current && !alternatives.contains(&source) {
+                alternatives.push(source);
+            }
+        }
+
+        alternatives
+    }
+}
+
+/// `Result<T, E>` -> `syn::Result<T>` に変換するトレイト
+///
+/// `res.into_syn(span)?;` のような使い方を想定
+pub trait IntoSynRes<T> {
+    fn into_syn(self, span: Span) -> syn::Result<T>;
+}
+
+impl<T, E> IntoSynRes<T> for Result<T, E>
+where
+    E: std::fmt::Display + std::fmt::Debug,
+{
+    fn into_syn(self, span: Span) -> syn::Result<T> {
+        match self {
+            Ok(ok) => Ok(ok),
+            Err(err) => Err(syn::Error::new(span, err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MacroInput;
+    use crate::sampling::ReasoningEffort;
+
+    #[test]
+    fn test_parse_sampling() {
+        let input: MacroInput = syn::parse_str(
+            r#"
+@temperature = 0;
+@top_p = 0.9;
+@reasoning_effort = "high";
+@stop = ["```", "END"];
+@n = 3;
+r"表示する(1)"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(input.sampling.temperature, Some(0.0));
+        assert_eq!(input.sampling.top_p, Some(0.9));
+        assert_eq!(input.sampling.reasoning_effort, Some(ReasoningEffort::High));
+        assert_eq!(
+            input.sampling.stop,
+            Some(vec!["```".to_string(), "END".to_string()])
+        );
+        assert_eq!(input.sampling.n, Some(3));
+
+        let input: Result<MacroInput, _> =
+            syn::parse_str(r#"@reasoning_effort = "max"; r"表示する(1)""#);
+        assert!(input.is_err());
+    }
+
+    #[test]
+    fn test_parse_prompt_extra() {
+        let input: MacroInput = syn::parse_str(
+            r#"
+@prompt_extra = "二進で表示する(x) は x を2進数で表示する";
+@prompt_extra = "整数(x) は x の小数点以下を切り捨てる";
+r"二進で表示する(整数(3.5))"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(input.prompt_extras.len(), 2);
+        assert_eq!(
+            input.prompt_extras[0],
+            "二進で表示する(x) は x を2進数で表示する"
+        );
+
+        let input: Result<MacroInput, _> =
+            syn::parse_str(r#"@prompt_file = "not_found.md"; r"表示する(1)""#);
+        assert!(input.is_err());
+
+        // `@allow_deps` の指示は `@prompt_extra` より前に置かれる
+        let input: MacroInput =
+            syn::parse_str(r#"@allow_deps = []; @prompt_extra = "補足"; r"表示する(1)""#).unwrap();
+        assert_eq!(input.allow_deps, Some(Vec::new()));
+        assert_eq!(input.extras().len(), 2);
+        assert!(input.extras()[0].contains("標準ライブラリ"));
+    }
+
+    #[test]
+    fn test_dncl_source() {
+        // 文字列リテラルの中の `;` は改行にならない。前後の空行は正規化で取り除かれる
+        let input: MacroInput = syn::parse_str("r#\"\nx = \"a;b\"\n表示する(x)\n\"#").unwrap();
+        assert_eq!(input.dncl_source(), "x = \"a;b\"\n表示する(x)");
+        // 正規化前や以前の方式のキャッシュキーも探せる
+        let alternatives = input.alternative_dncl_sources();
+        assert!(alternatives.contains(&"\nx = \"a;b\"\n表示する(x)\n".to_string()));
+        assert!(alternatives.contains(&"\"\nx = \\\"a\nb\\\"\n表示する(x)\n\"".to_string()));
+
+        let input: MacroInput = syn::parse_str("x = \"a;b\";\n表示する(x);").unwrap();
+        assert_eq!(input.dncl_source(), "x = \"a;b\"\n表示する(x)");
+    }
+}
@@ -0,0 +1,145 @@
+//! 試験問題からコピーしたDNCLコードの正規化
+//!
+//! 仕様の「補足」でモデルに任せていた、行番号・余分な改行や空白・全角文字などの揺れを、
+//! プロンプトを作る前にこちらで取り除く。キャッシュキーにも正規化後のコードを使うため、
+//! 見た目だけの編集では再度APIを叩かない
+
+use crate::source::strip_line_number;
+
+/// PDFからのコピー等で紛れ込む、取り除く文字 (ゼロ幅文字、BOM、ソフトハイフン)
+const REMOVED: &[char] = &[
+    '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}', '\u{AD}',
+];
+
+/// 文字列リテラルの外にある文字を半角に揃える
+fn to_half_width(c: char) -> char {
+    match c {
+        // 全角の英数字・記号 ( `＝` 、 `＜` 、 `（` 、 `１` など)
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' | '\u{A0}' => ' ',
+        // マイナス記号・ハイフンの異体字
+        '\u{2212}' | '\u{2010}' | '\u{2011}' | '\u{2013}' => '-',
+        _ => c,
+    }
+}
+
+/// 文字列リテラル ( `"…"` ・ `「…」` ) の外だけを半角に揃え、空白の並びを1つにする
+fn normalize_body(body: &str) -> String {
+    let mut normalized = String::new();
+    let mut closing: Option<char> = None;
+
+    for c in body.chars() {
+        match closing {
+            Some(close) => {
+                if c == close {
+                    closing = None;
+                }
+                normalized.push(c);
+            }
+            None => {
+                let c = to_half_width(c);
+                match c {
+                    '"' => closing = Some('"'),
+                    '「' => closing = Some('」'),
+                    _ => {}
+                }
+
+                if c.is_whitespace() && normalized.ends_with(' ') {
+                    continue;
+                }
+                normalized.push(if c.is_whitespace() { ' ' } else { c });
+            }
+        }
+    }
+
+    normalized.trim().to_string()
+}
+
+/// 行頭の制御範囲の記号を `│` ・ `└` に揃え、字下げとともに取り出す
+///
+/// 全角空白の字下げは半角2つ分とする
+fn split_bars(line: &str) -> (String, &str) {
+    let indent_len = line.len() - line.trim_start().len();
+    let mut prefix = line[..indent_len]
+        .replace('\u{3000}', "  ")
+        .replace('\t', "    ")
+        .replace('\u{A0}', " ");
+    let mut rest = &line[indent_len..];
+    let mut bars = Vec::new();
+
+    loop {
+        if let Some(r) = rest.strip_prefix("|=") {
+            bars.push('└');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix(['└', '┗']) {
+            bars.push('└');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix(['│', '┃', '|', '｜']) {
+            bars.push('│');
+            rest = r;
+        } else {
+            break;
+        }
+        rest = rest.trim_start();
+    }
+
+    for bar in bars {
+        prefix.push(bar);
+        prefix.push(' ');
+    }
+
+    (prefix, rest)
+}
+
+/// DNCLコードを正規化する
+///
+/// - 行頭の行番号を取り除く
+/// - 空行を取り除き、行末の空白を削る
+/// - 制御範囲の記号 ( `｜` ・ `┃` ・ `|` 、 `┗` ・ `|=` ) を `│` ・ `└` に揃える
+/// - 文字列リテラルの外の全角英数字・記号 ( `＝＜＞` など) や全角空白を半角にし、空白の並びを1つにする
+/// - ゼロ幅文字等を取り除く
+pub(crate) fn normalize(dncl_source: &str) -> String {
+    let dncl_source = dncl_source.replace(REMOVED, "");
+
+    dncl_source
+        .lines()
+        .filter_map(|line| {
+            // 行番号の後の空白は字下げとしない
+            let stripped = strip_line_number(line);
+            let line = match stripped.len() == line.len() {
+                true => line,
+                false => stripped.trim_start(),
+            };
+            let (prefix, body) = split_bars(line);
+            let body = normalize_body(body);
+
+            (!body.is_empty()).then(|| format!("{}{}", prefix, body))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalize;
+
+    #[test]
+    fn test_normalize() {
+        let source = "\n（１）Ｋｏｓｕｕ　＝　3\n\n(2) もし Kosuu ＞ 0 ならば:\n(3) ┃  表示する(\"ａ　　＝\",   Kosuu)\n(4) ┗ 表示する(「全角　のまま」)  \n";
+
+        assert_eq!(
+            normalize(source),
+            "Kosuu = 3\nもし Kosuu > 0 ならば:\n│ 表示する(\"ａ　　＝\", Kosuu)\n└ 表示する(「全角　のまま」)"
+        );
+
+        // 見た目だけが異なるコードは同じになる
+        assert_eq!(
+            normalize("x\u{200B} = 1\r\n｜ 表示する(x)"),
+            normalize("x = 1\n|表示する(x)")
+        );
+
+        // 行番号でない括弧や字下げは残る
+        assert_eq!(normalize("  (x + 1) を表示する"), "  (x + 1) を表示する");
+        assert_eq!(normalize("\u{3000}表示する(x)"), "  表示する(x)");
+    }
+}
@@ -0,0 +1,287 @@
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+use reqwest::blocking::{Client, RequestBuilder};
+use serde_json::Value;
+
+use crate::config::DEFAULT_BASE_URL;
+use crate::sampling::Sampling;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Role {
+    /// 仕様や注意事項など、モデルへの指示
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+pub(crate) struct Message {
+    pub role: Role,
+    pub content: String,
+}
+
+impl Message {
+    pub fn system(content: impl Into<String>) -> Self {
+        Message {
+            role: Role::System,
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Message {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Message {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// システムメッセージを受け付けるモデルか ( `o1-preview` ・ `o1-mini` は受け付けない)
+fn supports_system_role(model: &str) -> bool {
+    !(model.starts_with("o1-preview") || model.starts_with("o1-mini"))
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct RequestBody {
+    model: String,
+    messages: Vec<Message>,
+    seed: i64,
+    max_completion_tokens: Option<u32>,
+    #[serde(flatten)]
+    sampling: Sampling,
+}
+
+/// APIが返すトークン使用量
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl Usage {
+    /// 複数回のリクエストの使用量を合算する
+    pub fn add(self, other: &Usage) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+/// 返答本文 ( `n` を指定した場合は複数) とプロバイダのメタデータ
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub choices: Vec<String>,
+    pub system_fingerprint: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Clone)]
+pub struct QuerySetting {
+    pub api_key: String,
+    pub model: String,
+    pub seed: i64,
+    pub max_completion_tokens: Option<u32>,
+    /// APIのベースURL。 `None` の場合はOpenAI API
+    pub base_url: Option<String>,
+    pub sampling: Sampling,
+    /// 条件を満たす候補が得られるまでに行うリクエストの最大回数
+    pub best_of: Option<u32>,
+    /// キャッシュディレクトリ (キャッシュキーには含めない)
+    pub cache_dir: PathBuf,
+}
+
+// 既存のキャッシュキーを変えないよう、後から追加した項目は指定された場合のみハッシュに含める
+impl Hash for QuerySetting {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.api_key.hash(state);
+        self.model.hash(state);
+        self.seed.hash(state);
+        self.max_completion_tokens.hash(state);
+
+        if let Some(base_url) = &self.base_url {
+            ("base_url", base_url).hash(state);
+        }
+        self.sampling.hash(state);
+        if let Some(best_of) = self.best_of {
+            ("best_of", best_of).hash(state);
+        }
+    }
+}
+
+impl QuerySetting {
+    fn header(&self) -> RequestBuilder {
+        let api_key_field = format!("Bearer {}", self.api_key);
+        let base_url = self.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+
+        Client::new()
+            .post(format!("{}/chat/completions", base_url))
+            .header("Content-Type", "application/json")
+            .header("Authorization", api_key_field.as_str())
+    }
+
+    fn make_request_body(&self, messages: &[Message]) -> RequestBody {
+        // システムメッセージを受け付けないモデルには、ユーザーのメッセージとして送る
+        let system_role = supports_system_role(&self.model);
+        let messages = messages
+            .iter()
+            .map(|message| match message.role {
+                Role::System if !system_role => Message::user(message.content.clone()),
+                _ => message.clone(),
+            })
+            .collect();
+
+        let Self {
+            model,
+            seed,
+            max_completion_tokens,
+            sampling,
+            ..
+        } = self.clone();
+
+        RequestBody {
+            model,
+            messages,
+            seed,
+            max_completion_tokens,
+            sampling,
+        }
+    }
+
+    pub fn query(&self, input_messages: &[Message]) -> eyre::Result<Completion> {
+        let body: Value = self
+            .header()
+            .json(&self.make_request_body(input_messages))
+            .send()?
+            .json()?;
+
+        let mut choices = match &body["choices"] {
+            Value::Array(choices) => choices
+                .iter()
+                .filter_map(|choice| match &choice["message"]["content"] {
+                    Value::String(s) => Some(s.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        if choices.is_empty() {
+            choices.push(format!("[Unexpected response]\n{}", body));
+        }
+
+        let system_fingerprint = match &body["system_fingerprint"] {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        };
+
+        let usage = serde_json::from_value(body["usage"].clone()).ok();
+
+        Ok(Completion {
+            choices,
+            system_fingerprint,
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Message, QuerySetting, Role};
+    use crate::cache::hash_content;
+
+    #[derive(Hash)]
+    struct OldQuerySetting {
+        api_key: String,
+        model: String,
+        seed: i64,
+        max_completion_tokens: Option<u32>,
+    }
+
+    #[test]
+    fn test_hash_compatibility() {
+        let setting = QuerySetting {
+            api_key: "key".to_string(),
+            model: "gpt-4o".to_string(),
+            seed: 123456,
+            max_completion_tokens: Some(4096),
+            base_url: None,
+            sampling: Default::default(),
+            best_of: None,
+            cache_dir: "gpt_responses".into(),
+        };
+        let old = OldQuerySetting {
+            api_key: "key".to_string(),
+            model: "gpt-4o".to_string(),
+            seed: 123456,
+            max_completion_tokens: Some(4096),
+        };
+
+        // 追加項目が未指定ならキャッシュキーは変わらない
+        assert_eq!(hash_content(&(&setting, "a")), hash_content(&(&old, "a")));
+
+        let setting = QuerySetting {
+            base_url: Some("http://localhost:11434/v1".to_string()),
+            ..setting
+        };
+        assert_ne!(hash_content(&(&setting, "a")), hash_content(&(&old, "a")));
+    }
+
+    #[test]
+    fn test_system_role() {
+        let setting = |model: &str| QuerySetting {
+            api_key: "key".to_string(),
+            model: model.to_string(),
+            seed: 0,
+            max_completion_tokens: None,
+            base_url: None,
+            sampling: Default::default(),
+            best_of: None,
+            cache_dir: "gpt_responses".into(),
+        };
+        let messages = [Message::system("仕様"), Message::user("コード")];
+
+        let body = setting("gpt-4o").make_request_body(&messages);
+        assert_eq!(body.messages, messages);
+
+        // システムメッセージを受け付けないモデルではユーザーのメッセージにする
+        let body = setting("o1-preview").make_request_body(&messages);
+        assert!(body
+            .messages
+            .iter()
+            .all(|message| message.role == Role::User));
+    }
+
+    #[test]
+    fn test_query() {
+        dotenvy::dotenv().ok();
+
+        let setting = QuerySetting {
+            api_key: std::env::var("OPENAI_API_KEY").unwrap(),
+            model: "gpt-3.5-turbo".to_string(),
+            seed: 123456,
+            max_completion_tokens: Some(4096),
+            base_url: None,
+            sampling: Default::default(),
+            best_of: None,
+            cache_dir: "gpt_responses".into(),
+        };
+
+        let response = setting
+            .query(&[Message::user("Hello, how are you?")])
+            .unwrap();
+
+        dbg!(response);
+    }
+}
@@ -0,0 +1,88 @@
+//! `乱数()` の扱い
+//!
+//! `rand` クレートに依存しないよう、シード付きの擬似乱数生成器 (splitmix64) を `dncl_random()` として生成コードと一緒に展開する。
+//! シード値は環境変数 `DNCL_RNG_SEED` 、 `@rng_seed` 、現在時刻の順に決まる
+
+use syn::ItemFn;
+
+use crate::source::{lines, Line};
+use crate::token::{tokenize, Kind};
+
+/// 乱数を返す関数名
+pub(crate) const RANDOM_FN: &str = "dncl_random";
+
+/// 実行時にシード値を与える環境変数名
+pub const RNG_SEED_ENV: &str = "DNCL_RNG_SEED";
+
+/// `乱数()` を呼び出しているか (コメントや文字列リテラルの中は除く)
+pub(crate) fn uses_random(dncl_source: &str) -> bool {
+    lines(dncl_source).iter().any(|Line { text, .. }| {
+        tokenize(text).windows(2).any(|pair| {
+            matches!(pair[0].kind, Kind::Ident | Kind::Word)
+                && pair[0].text == "乱数"
+                && pair[1].kind == Kind::Open('(')
+        })
+    })
+}
+
+/// 生成コードと一緒に展開する `dncl_random` 関数
+pub(crate) fn random_fn(seed: Option<u64>) -> ItemFn {
+    let seed = match seed {
+        Some(seed) => quote::quote! { #seed },
+        None => quote::quote! {
+            ::std::time::SystemTime::now()
+                .duration_since(::std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0)
+        },
+    };
+
+    syn::parse_quote! {
+        /// 0 以上 1 未満の乱数
+        #[allow(dead_code)]
+        fn dncl_random() -> f64 {
+            static STATE: ::std::sync::Mutex<Option<u64>> = ::std::sync::Mutex::new(None);
+
+            let mut state = STATE.lock().unwrap();
+            let state = state.get_or_insert_with(|| match ::std::env::var(#RNG_SEED_ENV) {
+                Ok(seed) => seed
+                    .trim()
+                    .parse()
+                    .expect("DNCL_RNG_SEED must be an unsigned integer"),
+                Err(_) => #seed,
+            });
+
+            // splitmix64
+            *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = *state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+
+            (z >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{random_fn, uses_random};
+
+    #[test]
+    fn test_random_fn() {
+        assert!(uses_random("saikoro = 整数(乱数() * 6) + 1"));
+        assert!(!uses_random("x = 1"));
+        assert!(uses_random("x = 乱数（）"));
+        // コメントや文字列リテラルの中、呼び出しでない語は対象外
+        assert!(!uses_random("x = 1 # 乱数は使わない"));
+        assert!(!uses_random("表示する(\"乱数()\")"));
+        assert!(!uses_random("表示する(「乱数」)"));
+
+        let item_fn = random_fn(Some(42));
+        let code = quote::quote!(#item_fn).to_string();
+
+        assert_eq!(item_fn.sig.ident, "dncl_random");
+        assert!(code.contains("42u64"));
+        assert!(!random_fn(None).block.stmts.is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::hash::{Hash, Hasher};
+
+use crate::config::Provider;
+
+/// o系列 (推論) モデルの推論の深さ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl std::str::FromStr for ReasoningEffort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => Err(format!(
+                "reasoning_effort must be one of \"low\", \"medium\" or \"high\", but got \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+/// サンプリングに関するパラメータ。未指定の項目はAPIへ送らない
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Sampling {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+}
+
+// 既存のキャッシュキーを変えないよう、指定された項目のみハッシュに含める
+impl Hash for Sampling {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(temperature) = self.temperature {
+            ("temperature", temperature.to_bits()).hash(state);
+        }
+        if let Some(top_p) = self.top_p {
+            ("top_p", top_p.to_bits()).hash(state);
+        }
+        if let Some(reasoning_effort) = self.reasoning_effort {
+            ("reasoning_effort", reasoning_effort).hash(state);
+        }
+        if let Some(stop) = &self.stop {
+            ("stop", stop).hash(state);
+        }
+        if let Some(n) = self.n {
+            ("n", n).hash(state);
+        }
+    }
+}
+
+/// o1, o3-mini 等の推論モデルかどうか
+fn is_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+impl Sampling {
+    /// 未指定の項目を `other` の値で補う
+    pub fn or(self, other: Sampling) -> Sampling {
+        Sampling {
+            temperature: self.temperature.or(other.temperature),
+            top_p: self.top_p.or(other.top_p),
+            reasoning_effort: self.reasoning_effort.or(other.reasoning_effort),
+            stop: self.stop.or(other.stop),
+            n: self.n.or(other.n),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Sampling::default()
+    }
+
+    /// 値の範囲と、プロバイダ・モデルごとの対応状況を確認する
+    pub fn validate(&self, provider: Provider, model: &str) -> Result<(), String> {
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!(
+                    "temperature must be between 0 and 2, but got {}",
+                    temperature
+                ));
+            }
+        }
+
+        if let Some(top_p) = self.top_p {
+            if !(top_p > 0.0 && top_p <= 1.0) {
+                return Err(format!("top_p must be in (0, 1], but got {}", top_p));
+            }
+        }
+
+        if self.n == Some(0) {
+            return Err("n must be at least 1".to_string());
+        }
+
+        // OpenAI互換APIの対応状況はまちまちなので、そのまま送る
+        if provider != Provider::OpenAi {
+            return Ok(());
+        }
+
+        if let Some(stop) = &self.stop {
+            if stop.len() > 4 {
+                return Err(format!(
+                    "OpenAI API accepts up to 4 stop sequences, but got {}",
+                    stop.len()
+                ));
+            }
+        }
+
+        match is_reasoning_model(model) {
+            true if self.temperature.is_some_and(|t| t != 1.0) => Err(format!(
+                "temperature is not supported by reasoning model {}",
+                model
+            )),
+            true if self.top_p.is_some_and(|p| p != 1.0) => Err(format!(
+                "top_p is not supported by reasoning model {}",
+                model
+            )),
+            false if self.reasoning_effort.is_some() => Err(format!(
+                "reasoning_effort is only supported by reasoning models (o1, o3-mini, ...), not {}",
+                model
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_reasoning_model, ReasoningEffort, Sampling};
+    use crate::cache::hash_content;
+    use crate::config::Provider;
+
+    #[test]
+    fn test_is_reasoning_model() {
+        assert!(is_reasoning_model("o1"));
+        assert!(is_reasoning_model("o1-preview"));
+        assert!(is_reasoning_model("o3-mini"));
+        assert!(!is_reasoning_model("gpt-4o"));
+        assert!(!is_reasoning_model("omni"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let openai = Provider::OpenAi;
+
+        let sampling = Sampling {
+            temperature: Some(0.0),
+            stop: Some(vec!["```\n".to_string()]),
+            ..Sampling::default()
+        };
+        assert!(sampling.validate(openai, "gpt-4o").is_ok());
+        assert!(sampling.validate(openai, "o1-preview").is_err());
+
+        let sampling = Sampling {
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..Sampling::default()
+        };
+        assert!(sampling.validate(openai, "o3-mini").is_ok());
+        assert!(sampling.validate(openai, "gpt-4o").is_err());
+        assert!(sampling
+            .validate(Provider::OpenAiCompatible, "llama3")
+            .is_ok());
+
+        let sampling = Sampling {
+            temperature: Some(2.5),
+            ..Sampling::default()
+        };
+        assert!(sampling
+            .validate(Provider::OpenAiCompatible, "llama3")
+            .is_err());
+
+        let sampling = Sampling {
+            n: Some(0),
+            ..Sampling::default()
+        };
+        assert!(sampling.validate(openai, "gpt-4o").is_err());
+    }
+
+    #[test]
+    fn test_or_and_hash() {
+        let input = Sampling {
+            temperature: Some(0.0),
+            ..Sampling::default()
+        };
+        let config = Sampling {
+            temperature: Some(1.0),
+            n: Some(2),
+            ..Sampling::default()
+        };
+
+        let merged = input.or(config);
+        assert_eq!(merged.temperature, Some(0.0));
+        assert_eq!(merged.n, Some(2));
+
+        // 未指定の場合は何もハッシュしない
+        assert_eq!(hash_content(&Sampling::default()), hash_content(&()));
+        assert_ne!(hash_content(&merged), hash_content(&Sampling::default()));
+    }
+}
@@ -0,0 +1,633 @@
+//! 生成コードの安全性の確認
+//!
+//! マクロは返答をそのままユーザーのクレートへ展開するため、DNCLのコメント等でモデルが誘導されても
+//! 危険な操作を含むコードがコンパイルされないよう、 `@allow` で許可されていない操作を拒否する
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use proc_macro2::{TokenStream, TokenTree};
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
+use syn::{
+    Attribute, Expr, ExprUnsafe, ItemExternCrate, ItemForeignMod, ItemImpl, ItemMacro, ItemMod,
+    ItemTrait, ItemUse, Macro, Path, Signature, Token, UseTree,
+};
+
+use crate::candidate::visit_macro_args;
+
+/// `@allow` で許可できる操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    /// `unsafe` ブロック・関数、 `asm!` 、 `#[no_mangle]` 等
+    Unsafe,
+    /// `std::process`
+    Process,
+    /// `std::fs`
+    Fs,
+    /// `std::net`
+    Net,
+    /// `std::env` 、 `env!` 、 `option_env!`
+    Env,
+    /// `include!` 、 `include_str!` 、 `include_bytes!` 、ファイルから読み込む `mod`
+    Include,
+    /// `extern` ブロック、 `extern "C" fn` 、 `#[link]`
+    Extern,
+    /// `macro_rules!` によるマクロの定義 (展開後のコードを確認できないため)
+    Macro,
+}
+
+const CAPABILITIES: &[(&str, Capability)] = &[
+    ("unsafe", Capability::Unsafe),
+    ("process", Capability::Process),
+    ("fs", Capability::Fs),
+    ("net", Capability::Net),
+    ("env", Capability::Env),
+    ("include", Capability::Include),
+    ("extern", Capability::Extern),
+    ("macro", Capability::Macro),
+];
+
+impl FromStr for Capability {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CAPABILITIES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, capability)| *capability)
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "@allow must be some of {}, but got {:?}",
+                    CAPABILITIES
+                        .iter()
+                        .map(|(name, _)| format!("{:?}", name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    s
+                )
+            })
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = CAPABILITIES
+            .iter()
+            .find(|(_, capability)| capability == self)
+            .unwrap();
+
+        write!(f, "{}", name)
+    }
+}
+
+/// 生成コードの中で見つかった、許可が必要な操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Violation {
+    pub capability: Capability,
+    /// 該当箇所 ( `std::fs::read_to_string` など)
+    pub found: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "generated code uses `{}`, which is not allowed (specify `@allow = [\"{}\"]` to allow it)",
+            self.found, self.capability
+        )
+    }
+}
+
+/// 標準ライブラリのモジュールと対応する操作
+const STD_MODULES: &[(&str, Capability)] = &[
+    ("process", Capability::Process),
+    ("fs", Capability::Fs),
+    ("net", Capability::Net),
+    ("env", Capability::Env),
+];
+
+/// 操作の対象となるマクロ
+const MACROS: &[(&str, Capability)] = &[
+    ("include", Capability::Include),
+    ("include_str", Capability::Include),
+    ("include_bytes", Capability::Include),
+    ("env", Capability::Env),
+    ("option_env", Capability::Env),
+    ("asm", Capability::Unsafe),
+    ("global_asm", Capability::Unsafe),
+    ("naked_asm", Capability::Unsafe),
+];
+
+/// `unsafe` が必要な、あるいはリンクに影響する属性
+const ATTRIBUTES: &[(&str, Capability)] = &[
+    ("no_mangle", Capability::Unsafe),
+    ("export_name", Capability::Unsafe),
+    ("link_section", Capability::Unsafe),
+    ("unsafe", Capability::Unsafe),
+    ("link", Capability::Extern),
+    ("link_name", Capability::Extern),
+    ("path", Capability::Include),
+];
+
+/// 標準ライブラリのクレート名
+const STD_ROOTS: &[&str] = &["std", "core", "alloc"];
+
+/// `std::os::unix::fs` のように `std::os` の下にあっても対象となるモジュール
+const OS_MODULES: &[&str] = &["process", "fs", "net"];
+
+/// `use` ・ `extern crate` で標準ライブラリ (のモジュール) に付けられた名前を集める
+#[derive(Default)]
+struct AliasCollector {
+    /// 名前と、それが指す標準ライブラリのパス ( `s` → `["std"]` 、 `fs` → `["std", "fs"]` など)
+    aliases: HashMap<String, Vec<String>>,
+    /// `use std::*;` のようにグロブインポートされた標準ライブラリのパス
+    globs: Vec<Vec<String>>,
+}
+
+impl AliasCollector {
+    fn collect_use_tree(&mut self, tree: &UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.collect_use_tree(&path.tree, prefix);
+                prefix.pop();
+            }
+            UseTree::Name(name) if name.ident == "self" => {
+                if let Some(last) = prefix.last() {
+                    self.aliases.insert(last.clone(), prefix.clone());
+                }
+            }
+            UseTree::Name(name) => {
+                let mut segments = prefix.clone();
+                segments.push(name.ident.to_string());
+                self.aliases.insert(name.ident.to_string(), segments);
+            }
+            UseTree::Rename(rename) => {
+                let mut segments = prefix.clone();
+                if rename.ident != "self" {
+                    segments.push(rename.ident.to_string());
+                }
+                self.aliases.insert(rename.rename.to_string(), segments);
+            }
+            UseTree::Glob(_) => self.globs.push(prefix.clone()),
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.collect_use_tree(tree, prefix);
+                }
+            }
+        }
+    }
+
+    /// 別名を辿って標準ライブラリからのパスに直す。標準ライブラリのパスでなければ `None`
+    fn resolve(&self, segments: &[String]) -> Option<Vec<String>> {
+        let mut resolved = segments.to_vec();
+
+        // `use std as s; use s::os as o;` のような別名の別名も辿る (循環しても止まるよう回数を制限する)
+        for _ in 0..=self.aliases.len() {
+            let root = resolved.first()?;
+            if STD_ROOTS.contains(&root.as_str()) {
+                return Some(resolved);
+            }
+            let target = self.aliases.get(root)?;
+            resolved = target.iter().chain(&resolved[1..]).cloned().collect();
+        }
+
+        None
+    }
+
+    /// 標準ライブラリのパスの別名だけを残す
+    fn retain_std(mut self) -> Self {
+        let resolved = self
+            .aliases
+            .keys()
+            .filter(|name| self.resolve(&[(*name).clone()]).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        self.aliases.retain(|name, _| resolved.contains(name));
+
+        let globs = std::mem::take(&mut self.globs);
+        self.globs = globs.iter().filter_map(|glob| self.resolve(glob)).collect();
+
+        self
+    }
+}
+
+impl<'ast> Visit<'ast> for AliasCollector {
+    fn visit_item_use(&mut self, node: &'ast ItemUse) {
+        self.collect_use_tree(&node.tree, &mut Vec::new());
+    }
+
+    fn visit_item_extern_crate(&mut self, node: &'ast ItemExternCrate) {
+        if let Some((_, rename)) = &node.rename {
+            self.aliases
+                .insert(rename.to_string(), vec![node.ident.to_string()]);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scanner {
+    violations: Vec<Violation>,
+    aliases: AliasCollector,
+}
+
+impl Scanner {
+    fn push(&mut self, capability: Capability, found: impl Into<String>) {
+        self.violations.push(Violation {
+            capability,
+            found: found.into(),
+        });
+    }
+
+    /// `std::fs::...` のようなパスを確認する
+    ///
+    /// 標準ライブラリの別名から始まるパスや、グロブインポートした標準ライブラリのモジュールから始まるパスも対象とする
+    fn check_segments(&mut self, segments: &[String]) {
+        if segments.is_empty() {
+            return;
+        }
+
+        let candidates = match self.aliases.resolve(segments) {
+            Some(resolved) => vec![resolved],
+            // 1つの名前だけのパスは変数であることが多いので除く
+            None if segments.len() < 2 => return,
+            None => self
+                .aliases
+                .globs
+                .iter()
+                .map(|glob| glob.iter().chain(segments).cloned().collect())
+                .collect(),
+        };
+
+        for resolved in candidates {
+            if let Some(capability) = std_capability(&resolved) {
+                self.push(capability, segments.join("::"));
+                return;
+            }
+        }
+    }
+
+    /// `use` のパスを展開して確認する
+    fn check_use_tree(&mut self, tree: &UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.check_use_tree(&path.tree, prefix);
+                prefix.pop();
+            }
+            UseTree::Name(name) => {
+                let mut segments = prefix.clone();
+                if name.ident != "self" {
+                    segments.push(name.ident.to_string());
+                }
+                self.check_segments(&segments);
+            }
+            UseTree::Rename(rename) => {
+                let mut segments = prefix.clone();
+                if rename.ident != "self" {
+                    segments.push(rename.ident.to_string());
+                }
+                self.check_segments(&segments);
+            }
+            UseTree::Glob(_) => self.check_segments(prefix),
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.check_use_tree(tree, prefix);
+                }
+            }
+        }
+    }
+
+    fn check_attr(&mut self, attr: &Attribute) {
+        let Some(ident) = attr.path().get_ident() else {
+            return;
+        };
+        if let Some((name, capability)) = ATTRIBUTES.iter().find(|(name, _)| ident == name) {
+            self.push(*capability, format!("#[{}]", name));
+        }
+    }
+
+    fn check_signature(&mut self, sig: &Signature) {
+        if sig.unsafety.is_some() {
+            self.push(Capability::Unsafe, format!("unsafe fn {}", sig.ident));
+        }
+        if sig.abi.is_some() {
+            self.push(Capability::Extern, format!("extern fn {}", sig.ident));
+        }
+    }
+
+    /// 式として解析できないマクロの引数や `macro_rules!` の本体のトークン列から、パスを探して確認する
+    fn check_tokens(&mut self, tokens: TokenStream) {
+        let mut path: Vec<String> = Vec::new();
+        // 直前に続いている `:` の数
+        let mut colons = 0;
+
+        for token in tokens {
+            match token {
+                TokenTree::Ident(ident) => {
+                    if colons != 2 {
+                        self.check_segments(&std::mem::take(&mut path));
+                    }
+                    path.push(ident.to_string());
+                    colons = 0;
+                }
+                TokenTree::Punct(punct) if punct.as_char() == ':' => colons += 1,
+                TokenTree::Group(group) => {
+                    self.check_segments(&std::mem::take(&mut path));
+                    self.check_tokens(group.stream());
+                    colons = 0;
+                }
+                _ => {
+                    self.check_segments(&std::mem::take(&mut path));
+                    colons = 0;
+                }
+            }
+        }
+
+        self.check_segments(&path);
+    }
+}
+
+impl<'ast> Visit<'ast> for Scanner {
+    fn visit_attribute(&mut self, node: &'ast Attribute) {
+        self.check_attr(node);
+
+        visit::visit_attribute(self, node);
+    }
+
+    fn visit_item_use(&mut self, node: &'ast ItemUse) {
+        self.check_use_tree(&node.tree, &mut Vec::new());
+    }
+
+    fn visit_path(&mut self, node: &'ast Path) {
+        let segments = node
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>();
+        self.check_segments(&segments);
+
+        visit::visit_path(self, node);
+    }
+
+    fn visit_expr_unsafe(&mut self, node: &'ast ExprUnsafe) {
+        self.push(Capability::Unsafe, "unsafe { ... }");
+
+        visit::visit_expr_unsafe(self, node);
+    }
+
+    fn visit_signature(&mut self, node: &'ast Signature) {
+        self.check_signature(node);
+
+        visit::visit_signature(self, node);
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast ItemImpl) {
+        if node.unsafety.is_some() {
+            self.push(Capability::Unsafe, "unsafe impl");
+        }
+
+        visit::visit_item_impl(self, node);
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast ItemTrait) {
+        if node.unsafety.is_some() {
+            self.push(Capability::Unsafe, format!("unsafe trait {}", node.ident));
+        }
+
+        visit::visit_item_trait(self, node);
+    }
+
+    fn visit_item_foreign_mod(&mut self, node: &'ast ItemForeignMod) {
+        self.push(Capability::Extern, "extern { ... }");
+
+        visit::visit_item_foreign_mod(self, node);
+    }
+
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        // `mod foo;` は別のファイルを読み込む
+        if node.content.is_none() {
+            self.push(Capability::Include, format!("mod {};", node.ident));
+        }
+
+        visit::visit_item_mod(self, node);
+    }
+
+    fn visit_item_macro(&mut self, node: &'ast ItemMacro) {
+        if let Some(ident) = &node.ident {
+            self.push(Capability::Macro, format!("macro_rules! {}", ident));
+        }
+
+        visit::visit_item_macro(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if let Some(ident) = node.path.segments.last().map(|segment| &segment.ident) {
+            if let Some((name, capability)) = MACROS.iter().find(|(name, _)| ident == name) {
+                self.push(*capability, format!("{}!", name));
+            }
+        }
+
+        visit::visit_macro(self, node);
+
+        // 展開されるまで中身が分からないマクロは、トークン列の中のパスを確認する
+        let parsed = node.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated);
+        match parsed {
+            Ok(_) => visit_macro_args(self, node),
+            Err(_) => self.check_tokens(node.tokens.clone()),
+        }
+    }
+}
+
+/// 標準ライブラリのパスが対象となる操作
+fn std_capability(segments: &[String]) -> Option<Capability> {
+    let [root, module, rest @ ..] = segments else {
+        return None;
+    };
+    if !STD_ROOTS.contains(&root.as_str()) {
+        return None;
+    }
+
+    if let Some((_, capability)) = STD_MODULES.iter().find(|(name, _)| name == module) {
+        return Some(*capability);
+    }
+
+    // `std::os::unix::fs::symlink` ・ `std::os::unix::net::UnixStream` など
+    if module == "os" {
+        return rest.iter().find_map(|segment| {
+            STD_MODULES
+                .iter()
+                .find(|(name, _)| name == segment && OS_MODULES.contains(name))
+                .map(|(_, capability)| *capability)
+        });
+    }
+
+    None
+}
+
+/// 生成コードの中で、許可されていない操作を探す
+pub(crate) fn scan(file: &syn::File, allowed: &[Capability]) -> Vec<Violation> {
+    let mut aliases = AliasCollector::default();
+    aliases.visit_file(file);
+
+    let mut scanner = Scanner {
+        aliases: aliases.retain_std(),
+        ..Default::default()
+    };
+    scanner.visit_file(file);
+
+    scanner
+        .violations
+        .into_iter()
+        .filter(|violation| !allowed.contains(&violation.capability))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scan, Capability};
+
+    fn violations(code: &str, allowed: &[Capability]) -> Vec<Capability> {
+        let file = syn::parse_file(code).unwrap();
+
+        scan(&file, allowed)
+            .into_iter()
+            .map(|violation| violation.capability)
+            .collect()
+    }
+
+    #[test]
+    fn test_scan() {
+        assert!(violations(
+            "use std::io::{self, Write};\nfn main() { let x = 1; println!(\"{}\", x); io::stdout().flush().unwrap(); }",
+            &[],
+        )
+        .is_empty());
+
+        assert_eq!(
+            violations(
+                "fn main() { std::process::Command::new(\"sh\").status().unwrap(); }",
+                &[]
+            ),
+            vec![Capability::Process]
+        );
+        assert_eq!(
+            violations("use std::{fs, net::TcpStream};\nfn main() {}", &[]),
+            vec![Capability::Fs, Capability::Net]
+        );
+        assert_eq!(
+            violations(
+                "use std::*;\nfn main() { fs::remove_file(\"a\").ok(); }",
+                &[]
+            ),
+            vec![Capability::Fs]
+        );
+        assert_eq!(
+            violations(
+                "fn main() { let p: *const i64 = &1; println!(\"{}\", unsafe { *p }); }",
+                &[]
+            ),
+            vec![Capability::Unsafe]
+        );
+        assert_eq!(
+            violations(
+                "fn main() { println!(\"{}\", include_str!(\"/etc/passwd\")); }",
+                &[]
+            ),
+            vec![Capability::Include]
+        );
+        assert_eq!(
+            violations("extern \"C\" { fn abs(x: i32) -> i32; }\nfn main() {}", &[]),
+            vec![Capability::Extern]
+        );
+
+        // 展開されるまで分からないマクロの引数も確認する
+        assert_eq!(
+            violations(
+                "fn main() { let v = vec![std::fs::read(\"a\").unwrap(); 2]; }",
+                &[]
+            ),
+            vec![Capability::Fs]
+        );
+
+        // `@allow` で許可された操作は対象外
+        assert!(violations(
+            "fn main() { let home = env!(\"HOME\"); println!(\"{}\", home); }",
+            &[Capability::Env],
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_parse_capability() {
+        assert_eq!("fs".parse::<Capability>().unwrap(), Capability::Fs);
+        assert_eq!(Capability::Unsafe.to_string(), "unsafe");
+        assert!("network".parse::<Capability>().is_err());
+    }
+
+    #[test]
+    fn test_scan_aliases() {
+        assert_eq!(
+            violations("use std as s;\nfn main() { s::fs::read(\"a\").ok(); }", &[]),
+            vec![Capability::Fs]
+        );
+        assert_eq!(
+            violations(
+                "extern crate std as s2;\nfn main() { s2::process::Command::new(\"sh\").status().ok(); }",
+                &[]
+            ),
+            vec![Capability::Process]
+        );
+        // 別名の別名や、 `self` の別名も辿る
+        assert_eq!(
+            violations(
+                "use std::{self as s};\nuse s::os as o;\nfn main() { o::unix::fs::symlink(\"a\", \"b\").ok(); }",
+                &[]
+            ),
+            vec![Capability::Fs]
+        );
+        // 標準ライブラリ以外の別名は対象外
+        assert!(violations("use std::io as fs;\nfn main() { fs::stdout(); }", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_scan_std_os() {
+        assert_eq!(
+            violations(
+                "fn main() { std::os::unix::fs::symlink(\"a\", \"b\").ok(); }",
+                &[]
+            ),
+            vec![Capability::Fs]
+        );
+        assert_eq!(
+            violations("use std::os::unix::net::*;\nfn main() {}", &[]),
+            vec![Capability::Net]
+        );
+        assert_eq!(
+            violations(
+                "use std::os::unix::*;\nfn main() { process::parent_id(); }",
+                &[]
+            ),
+            vec![Capability::Process]
+        );
+    }
+
+    #[test]
+    fn test_scan_macro_rules() {
+        let code = "macro_rules! run {\n    ($m:ident) => { std::$m::Command::new(\"sh\").status().ok() };\n}\nfn main() { run!(process); }";
+
+        assert_eq!(violations(code, &[]), vec![Capability::Macro]);
+        assert!(violations(code, &[Capability::Macro]).is_empty());
+
+        // マクロを許可しても、本体に書かれたパスは確認する
+        assert_eq!(
+            violations(
+                "macro_rules! rm {\n    () => { std::fs::remove_file(\"a\").ok() };\n}\nfn main() { rm!(); }",
+                &[Capability::Macro]
+            ),
+            vec![Capability::Fs]
+        );
+    }
+}
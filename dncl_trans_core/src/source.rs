@@ -0,0 +1,420 @@
+//! DNCLソースコードの簡易的な解析
+
+use proc_macro2::{Delimiter, LineColumn, TokenStream, TokenTree};
+
+use crate::token::{tokenize, Kind};
+
+/// 文字列リテラル ( `「…」` ・ `"…"` ) の外で `sep` で分割する
+pub(crate) fn split_outside_strings<'a>(text: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut closing: Option<char> = None;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match closing {
+            Some(close) if c == close => closing = None,
+            Some(_) => {}
+            None if c == '「' => closing = Some('」'),
+            None if c == '"' => closing = Some('"'),
+            None if text[i..].starts_with(sep) => {
+                pieces.push(&text[start..i]);
+                start = i + sep.len();
+            }
+            None => {}
+        }
+    }
+    pieces.push(&text[start..]);
+
+    pieces
+}
+
+/// 行から `#` 以降のコメントを取り除く (文字列リテラル中の `#` はコメントではない)
+fn strip_comment(line: &str) -> &str {
+    split_outside_strings(line, "#")[0]
+}
+
+/// 行番号と制御範囲の記号を取り除いた1行
+#[derive(Debug, PartialEq)]
+pub(crate) struct Line {
+    /// `│` ・ `└` (または `|` ・ `|=` ) の数で表される制御文の入れ子の深さ
+    pub depth: usize,
+    pub text: String,
+}
+
+/// 行頭の `(1)` ・ `（1）` のような行番号を取り除く
+pub(crate) fn strip_line_number(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    let Some(rest) = trimmed.strip_prefix(['(', '（']) else {
+        return line;
+    };
+    let digits =
+        rest.trim_start_matches(|c: char| c.is_ascii_digit() || ('０'..='９').contains(&c));
+    if digits.len() == rest.len() {
+        return line;
+    }
+
+    digits.strip_prefix([')', '）']).unwrap_or(line)
+}
+
+/// コメント・行番号を取り除き、制御範囲の深さとともに空でない行を返す
+pub(crate) fn lines(dncl_source: &str) -> Vec<Line> {
+    dncl_source
+        .lines()
+        .map(|line| strip_line_number(strip_comment(line)))
+        .filter_map(|line| {
+            let mut depth = 0;
+            let mut rest = line.trim_start();
+
+            loop {
+                if let Some(r) = rest.strip_prefix("|=") {
+                    rest = r;
+                } else if let Some(r) = rest.strip_prefix(['│', '└', '|', '┃', '┗']) {
+                    rest = r;
+                } else {
+                    break;
+                }
+                depth += 1;
+                rest = rest.trim_start();
+            }
+
+            let text = rest.trim_end();
+            (!text.is_empty()).then(|| Line {
+                depth,
+                text: text.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `関数 名前(引数, ...) を 定義する:` で定義されている関数名
+pub(crate) fn defined_functions(dncl_source: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for line in dncl_source.lines().map(strip_comment) {
+        let Some((_, rest)) = line.split_once("関数") else {
+            continue;
+        };
+        if !rest.contains("定義する") {
+            continue;
+        }
+        let Some((name, _)) = rest.split_once(['(', '（']) else {
+            continue;
+        };
+
+        let name = name.trim();
+        if !name.is_empty() && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+
+    names
+}
+
+/// `Data[i, j]` のようにカンマ区切りの添字でアクセスされている配列名とその次元数
+pub(crate) fn multi_dim_arrays(dncl_source: &str) -> Vec<(String, usize)> {
+    let mut arrays: Vec<(String, usize)> = Vec::new();
+
+    for line in dncl_source.lines().map(strip_comment) {
+        let chars: Vec<char> = line.chars().collect();
+
+        for (open, _) in chars.iter().enumerate().filter(|(_, &c)| c == '[') {
+            // 直前の識別子が配列名 (トークン木で書かれた場合は空白が入る)
+            let end = chars[..open]
+                .iter()
+                .rposition(|c| !c.is_whitespace())
+                .map_or(0, |i| i + 1);
+            let start = chars[..end]
+                .iter()
+                .rposition(|c| !(c.is_alphanumeric() || *c == '_'))
+                .map_or(0, |i| i + 1);
+            if start == end {
+                continue;
+            }
+            let name: String = chars[start..end].iter().collect();
+
+            // 対応する `]` までのトップレベルのカンマを数える
+            let mut depth = 0;
+            let mut commas = 0;
+            for &c in &chars[open + 1..] {
+                match c {
+                    '[' | '(' => depth += 1,
+                    ']' | ')' if depth == 0 => break,
+                    ']' | ')' => depth -= 1,
+                    ',' if depth == 0 => commas += 1,
+                    _ => {}
+                }
+            }
+            if commas == 0 {
+                continue;
+            }
+
+            match arrays.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, dims)) => *dims = (*dims).max(commas + 1),
+                None => arrays.push((name, commas + 1)),
+            }
+        }
+    }
+
+    arrays
+}
+
+/// `Data[i]` のように添字でアクセスされている配列名
+pub(crate) fn indexed_arrays(dncl_source: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for Line { text, .. } in lines(dncl_source) {
+        let tokens = tokenize(&text);
+
+        for pair in tokens.windows(2) {
+            let is_array =
+                matches!(pair[0].kind, Kind::Ident | Kind::Word) && pair[1].kind == Kind::Open('[');
+            if is_array && !names.iter().any(|name| name == pair[0].text) {
+                names.push(pair[0].text.to_string());
+            }
+        }
+    }
+
+    names
+}
+
+/// トークン1つ (グループの括弧は開き・閉じそれぞれを1つとする) の文字列と位置
+struct Piece {
+    text: String,
+    start: LineColumn,
+    end: LineColumn,
+    /// 行の区切りとして扱う `;` か
+    separator: bool,
+}
+
+fn flatten(tokens: TokenStream, pieces: &mut Vec<Piece>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => {
+                        flatten(group.stream(), pieces);
+                        continue;
+                    }
+                };
+
+                pieces.push(Piece {
+                    text: open.to_string(),
+                    start: group.span_open().start(),
+                    end: group.span_open().end(),
+                    separator: false,
+                });
+                flatten(group.stream(), pieces);
+                pieces.push(Piece {
+                    text: close.to_string(),
+                    start: group.span_close().start(),
+                    end: group.span_close().end(),
+                    separator: false,
+                });
+            }
+            token => pieces.push(Piece {
+                text: token.to_string(),
+                start: token.span().start(),
+                end: token.span().end(),
+                separator: matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ';'),
+            }),
+        }
+    }
+}
+
+/// マクロに直接書かれたDNCLコードのトークン列から、元のソースコードを組み立てる
+///
+/// トークンの位置が分かる場合は、行・字下げ・トークン間の空白 (と `#` 以降のコメント) を再現し、
+/// 同じ行の中の `;` だけを改行にする。位置が分からない環境 (安定版コンパイラでのマクロ展開中など) では
+/// [`from_tokens_without_locations`] と同じになる
+pub(crate) fn from_tokens(tokens: TokenStream) -> String {
+    let mut pieces = Vec::new();
+    flatten(tokens.clone(), &mut pieces);
+
+    if pieces.iter().all(|piece| piece.start.line == 0) {
+        return from_tokens_without_locations(tokens);
+    }
+
+    // 各行の先頭のトークンのうち最も左にあるものを字下げの基準にする
+    let base = pieces
+        .iter()
+        .enumerate()
+        .filter(|(i, piece)| *i == 0 || pieces[i - 1].end.line < piece.start.line)
+        .map(|(_, piece)| piece.start.column)
+        .min()
+        .unwrap_or(0);
+
+    let mut source = String::new();
+    // 直前のトークンの終わり。 `None` は行頭
+    let mut prev: Option<LineColumn> = None;
+    let mut indent = 0;
+
+    for (i, piece) in pieces.iter().enumerate() {
+        if piece.separator {
+            // 同じ行に続きがある場合のみ改行にし、行末の `;` は取り除く
+            if pieces
+                .get(i + 1)
+                .is_some_and(|next| next.start.line == piece.end.line)
+            {
+                source.push('\n');
+                source.push_str(&" ".repeat(indent));
+                prev = None;
+            } else {
+                prev = Some(piece.end);
+            }
+            continue;
+        }
+
+        match prev {
+            Some(prev) if piece.start.line > prev.line => {
+                source.push_str(&"\n".repeat(piece.start.line - prev.line));
+                indent = piece.start.column.saturating_sub(base);
+                source.push_str(&" ".repeat(indent));
+            }
+            Some(prev) => {
+                source.push_str(&" ".repeat(piece.start.column.saturating_sub(prev.column)));
+            }
+            None if source.is_empty() => {
+                indent = piece.start.column.saturating_sub(base);
+                source.push_str(&" ".repeat(indent));
+            }
+            None => {}
+        }
+
+        source.push_str(&piece.text);
+        prev = Some(piece.end);
+    }
+
+    source
+}
+
+/// トークンの位置を使わずにDNCLコードを組み立てる
+///
+/// 文字列リテラルの中の `;` で区切らないよう、トークンとしての `;` でのみ行を区切る
+pub(crate) fn from_tokens_without_locations(tokens: TokenStream) -> String {
+    let mut lines = Vec::new();
+    let mut line = TokenStream::new();
+
+    for token in tokens {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {
+                lines.push(std::mem::take(&mut line).to_string());
+            }
+            _ => line.extend([token]),
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line.to_string());
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        defined_functions, from_tokens, from_tokens_without_locations, indexed_arrays, lines,
+        multi_dim_arrays, Line,
+    };
+
+    #[test]
+    fn test_defined_functions() {
+        let source = r##"
+(1) 関数 nibai(x) を 定義する:
+(2) └ x * 2 を返す
+関数 表示用（a, b）を定義する:
+└ 表示する(a, b)
+# 関数 comment(x) を 定義する
+表示する(nibai(3))
+表示する("#1") # 関数 after(x) を 定義する
+"##;
+
+        assert_eq!(defined_functions(source), vec!["nibai", "表示用"]);
+
+        // トークン木で書かれた場合は空白が入る
+        assert_eq!(
+            defined_functions("関数 hoge (x) を 定義する :\n└ x を 返す"),
+            vec!["hoge"]
+        );
+    }
+
+    #[test]
+    fn test_multi_dim_arrays() {
+        let source = r##"
+Data = [[1, 2, 3], [4, 5, 6]]
+表示する(Data[1, 2], Tokuten[3])
+Data[i, Ten[j]] = Box[a, b, c] # Memo[1, 2]
+表示する("#", Grid[1, 2])
+"##;
+
+        assert_eq!(
+            multi_dim_arrays(source),
+            vec![
+                ("Data".to_string(), 2),
+                ("Box".to_string(), 3),
+                ("Grid".to_string(), 2)
+            ]
+        );
+        assert_eq!(
+            multi_dim_arrays("表示する (Data [1 , 2])"),
+            vec![("Data".to_string(), 2)]
+        );
+        assert_eq!(
+            indexed_arrays(source),
+            vec!["Data", "Tokuten", "Ten", "Box", "Grid"]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let source = r##"
+(1) x = 3 # コメント
+表示する("#1", 「#2」)
+(2) もし x < 3 ならば:
+(3) │ x = x + 1
+(4) │ もし x == 3 ならば:
+(5) └ └ 表示する(x)
+
+|= y = 1
+"##;
+        let depth_and_text = |depth: usize, text: &str| Line {
+            depth,
+            text: text.to_string(),
+        };
+
+        assert_eq!(
+            lines(source),
+            vec![
+                depth_and_text(0, "x = 3"),
+                depth_and_text(0, "表示する(\"#1\", 「#2」)"),
+                depth_and_text(0, "もし x < 3 ならば:"),
+                depth_and_text(1, "x = x + 1"),
+                depth_and_text(1, "もし x == 3 ならば:"),
+                depth_and_text(2, "表示する(x)"),
+                depth_and_text(1, "y = 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_tokens() {
+        let tokens = r#"x = 1; y = "a;b"
+# コメント
+もし x > 0 ならば:
+    表示する(x, y)
+"#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            from_tokens(tokens),
+            "x = 1\ny = \"a;b\"\n# コメント\nもし x > 0 ならば:\n    表示する(x, y)"
+        );
+
+        // 位置が分からない場合も文字列中の `;` では区切らない
+        let tokens = r#"x = 1; y = "a;b";"#.parse().unwrap();
+        assert_eq!(from_tokens_without_locations(tokens), "x = 1\ny = \"a;b\"");
+    }
+}
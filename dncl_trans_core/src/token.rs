@@ -0,0 +1,196 @@
+//! DNCLコードの1行の字句解析
+//!
+//! 整形 ( [`crate::format`] ) ・約束事のチェック ( [`crate::lint`] ) ・型推論で共通して使う。
+//! 全角の括弧・読点や `≠` 等の記号もここで扱い、演算子の表は [`OPERATORS`] の1つだけとする
+
+/// 演算子 (長いものから順に照合する)
+const OPERATORS: &[&str] = &[
+    "**", "==", "!=", "<=", ">=", "=", "<", ">", "+", "-", "*", "/", "%", "÷", "×", "←", "≠", "≧",
+    "≦", "＋", "－",
+];
+
+/// 演算子として扱う英単語
+const WORD_OPERATORS: &[&str] = &["and", "or", "not", "div"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    /// 英字で始まる変数名・関数名
+    Ident,
+    Num,
+    /// 日本語の語 ( `表示する` ・ `ならば` など)
+    Word,
+    /// 文字列リテラル ( `"…"` ・ `「…」` )
+    Str,
+    /// `{外部からの入力}`
+    Input,
+    Op,
+    /// 開き括弧。全角の `（` も `(` とする
+    Open(char),
+    Close(char),
+    /// `,` と `、`
+    Comma,
+    Colon,
+    /// `#` 以降
+    Comment,
+    /// 上のいずれでもない1文字
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Token<'a> {
+    pub kind: Kind,
+    /// 元のコードの表記
+    pub text: &'a str,
+    /// 前に空白があったか
+    pub spaced: bool,
+    /// 行の中での開始位置 (バイト単位)
+    pub start: usize,
+}
+
+impl Token<'_> {
+    /// 種類と表記が一致するか
+    pub fn is(&self, kind: Kind, text: &str) -> bool {
+        self.kind == kind && self.text == text
+    }
+
+    /// 変数・数値・リテラル・閉じ括弧のように、値の終わりになるトークンか
+    pub fn ends_value(&self) -> bool {
+        matches!(
+            self.kind,
+            Kind::Ident | Kind::Num | Kind::Str | Kind::Input | Kind::Close(_)
+        )
+    }
+}
+
+/// 開き記号に対応する閉じ記号まで ( `"…"` ・ `「…」` ・ `{…}` ) の長さ
+fn enclosed_len(rest: &str, open: char, close: &[char]) -> usize {
+    rest[open.len_utf8()..].find(close).map_or(rest.len(), |i| {
+        let end = i + open.len_utf8();
+        end + rest[end..].chars().next().map_or(0, char::len_utf8)
+    })
+}
+
+/// 1行分のコードをトークンに分ける
+pub(crate) fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    loop {
+        let trimmed = rest.trim_start();
+        let spaced = trimmed.len() != rest.len();
+        rest = trimmed;
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+
+        let (kind, len) = match c {
+            '#' => (Kind::Comment, rest.trim_end().len()),
+            '"' => (Kind::Str, enclosed_len(rest, c, &['"'])),
+            '「' => (Kind::Str, enclosed_len(rest, c, &['」'])),
+            '{' | '｛' => (Kind::Input, enclosed_len(rest, c, &['}', '｝'])),
+            '(' | '（' => (Kind::Open('('), c.len_utf8()),
+            '[' => (Kind::Open('['), 1),
+            ')' | '）' => (Kind::Close(')'), c.len_utf8()),
+            ']' => (Kind::Close(']'), 1),
+            ',' | '、' => (Kind::Comma, c.len_utf8()),
+            ':' | '：' => (Kind::Colon, c.len_utf8()),
+            '0'..='9' => (
+                Kind::Num,
+                rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len()),
+            ),
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                match WORD_OPERATORS.contains(&&rest[..len]) {
+                    true => (Kind::Op, len),
+                    false => (Kind::Ident, len),
+                }
+            }
+            _ => match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => (Kind::Op, op.len()),
+                None if c.is_alphanumeric() => (
+                    Kind::Word,
+                    rest.find(|c: char| c.is_ascii() || !c.is_alphanumeric())
+                        .unwrap_or(rest.len()),
+                ),
+                None => (Kind::Other, c.len_utf8()),
+            },
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &rest[..len],
+            spaced,
+            start: text.len() - rest.len(),
+        });
+        rest = &rest[len..];
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tokenize, Kind};
+
+    #[test]
+    fn test_tokenize() {
+        let kinds = |text| {
+            tokenize(text)
+                .into_iter()
+                .map(|token| (token.kind, token.text))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds("Data[i, j] ← x≠-1 and y"),
+            vec![
+                (Kind::Ident, "Data"),
+                (Kind::Open('['), "["),
+                (Kind::Ident, "i"),
+                (Kind::Comma, ","),
+                (Kind::Ident, "j"),
+                (Kind::Close(']'), "]"),
+                (Kind::Op, "←"),
+                (Kind::Ident, "x"),
+                (Kind::Op, "≠"),
+                (Kind::Op, "-"),
+                (Kind::Num, "1"),
+                (Kind::Op, "and"),
+                (Kind::Ident, "y"),
+            ]
+        );
+        assert_eq!(
+            kinds("表示する（\"a#b\"、{外部からの入力}）# 表示"),
+            vec![
+                (Kind::Word, "表示する"),
+                (Kind::Open('('), "（"),
+                (Kind::Str, "\"a#b\""),
+                (Kind::Comma, "、"),
+                (Kind::Input, "{外部からの入力}"),
+                (Kind::Close(')'), "）"),
+                (Kind::Comment, "# 表示"),
+            ]
+        );
+        assert_eq!(
+            kinds("iを0から9まで"),
+            vec![
+                (Kind::Ident, "i"),
+                (Kind::Word, "を"),
+                (Kind::Num, "0"),
+                (Kind::Word, "から"),
+                (Kind::Num, "9"),
+                (Kind::Word, "まで"),
+            ]
+        );
+
+        let spaced = tokenize("x =y")
+            .into_iter()
+            .map(|token| token.spaced)
+            .collect::<Vec<_>>();
+        assert_eq!(spaced, vec![false, true, false]);
+        assert_eq!(tokenize("xを 1")[2].start, "xを ".len());
+    }
+}
@@ -2,7 +2,7 @@
 
 use proc_macro::TokenStream;
 
-mod impls;
+use dncl_trans_core::MacroInput;
 
 /// DNCLプログラムをRustプログラムにトランスパイルするマクロ
 ///
@@ -22,9 +22,9 @@ mod impls;
 /// ```
 #[proc_macro]
 pub fn dncl(input: TokenStream) -> TokenStream {
-    let input = syn::parse_macro_input!(input as impls::MacroInput);
+    let input = syn::parse_macro_input!(input as MacroInput);
 
-    impls::dncl_impl(input)
+    dncl_trans_core::dncl_impl(input)
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }